@@ -0,0 +1,98 @@
+/// A set of characters given as inclusive ranges, as written in a bracket expression
+/// such as `[a-z0-9_]` or `[^aeiou]`.
+///
+/// `ranges` is always normalized: sorted by start, with no two ranges overlapping or touching.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CharClass {
+    pub ranges: Vec<(char, char)>,
+    /// If true, the class matches every character *not* in `ranges`.
+    pub negated: bool,
+}
+
+impl CharClass {
+    pub fn new(ranges: Vec<(char, char)>, negated: bool) -> CharClass {
+        CharClass {
+            ranges: normalize_ranges(ranges),
+            negated,
+        }
+    }
+
+    /// Returns the normalized ranges of all characters matched by this class, with any
+    /// negation already applied.
+    pub fn matched_ranges(&self) -> Vec<(char, char)> {
+        if self.negated {
+            negate_ranges(&self.ranges)
+        } else {
+            self.ranges.clone()
+        }
+    }
+}
+
+/// Returns the character directly after `c`, skipping over the surrogate code points which are
+/// not valid `char`s.
+pub fn next_char(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// Returns the character directly before `c`, skipping over the surrogate code points which are
+/// not valid `char`s.
+pub fn prev_char(c: char) -> Option<char> {
+    match c {
+        '\u{E000}' => Some('\u{D7FF}'),
+        '\0' => None,
+        _ => char::from_u32(c as u32 - 1),
+    }
+}
+
+/// Sorts `ranges` and merges every pair of ranges that overlap or touch.
+pub fn normalize_ranges(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort();
+
+    let mut normalized: Vec<(char, char)> = Vec::new();
+    for (start, end) in ranges {
+        match normalized.last_mut() {
+            Some((_, last_end)) if next_char(*last_end).is_none_or(|next| start <= next) => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => normalized.push((start, end)),
+        }
+    }
+    normalized
+}
+
+/// Returns the normalized ranges of every character not covered by the normalized `ranges`.
+pub fn negate_ranges(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut negated = Vec::new();
+    let mut next_start = Some('\0');
+    for (start, end) in ranges {
+        if let (Some(gap_start), Some(gap_end)) = (next_start, prev_char(*start)) {
+            if gap_start <= gap_end {
+                negated.push((gap_start, gap_end));
+            }
+        }
+        next_start = next_char(*end);
+    }
+    if let Some(gap_start) = next_start {
+        negated.push((gap_start, char::MAX));
+    }
+    negated
+}
+
+/// Returns true if `c` lies inside one of the normalized `ranges`.
+pub fn ranges_contain(ranges: &[(char, char)], c: char) -> bool {
+    ranges
+        .binary_search_by(|(start, end)| {
+            if *end < c {
+                std::cmp::Ordering::Less
+            } else if *start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
@@ -0,0 +1,471 @@
+mod tests;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    char_class::{next_char, normalize_ranges, prev_char},
+    stage_3::{do_epsilon_closure, do_transition},
+    Label, Nfa, State,
+};
+
+/// The number of states `determinize` allows a dfa to have before giving up.
+pub const DEFAULT_STATE_LIMIT: usize = 10_000;
+
+/// A complete deterministic finite automaton over all of `char`.
+///
+/// The characters are split into classes of consecutive characters which the automaton never
+/// tells apart, so the transition table only needs one column per class rather than per character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dfa {
+    pub initial_state: State,
+    /// `accepting[q]` is true if state q is accepting. The dfa has `accepting.len()` states.
+    pub accepting: Vec<bool>,
+    /// The sorted, disjoint ranges of the character classes, together covering every `char`.
+    pub classes: Vec<(char, char)>,
+    /// `transitions[q * classes.len() + i]` is the state reached from state q upon reading a
+    /// character of class i.
+    pub transitions: Vec<State>,
+}
+
+impl Dfa {
+    pub fn num_states(&self) -> usize {
+        self.accepting.len()
+    }
+
+    /// Returns the index of the class containing `c`.
+    pub fn class_of(&self, c: char) -> usize {
+        self.classes.partition_point(|(start, _)| *start <= c) - 1
+    }
+
+    pub fn next_state(&self, state: State, c: char) -> State {
+        self.transitions[state * self.classes.len() + self.class_of(c)]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DfaError {
+    /// The dfa would need more than `limit` states.
+    TooManyStates { limit: usize },
+}
+
+impl std::fmt::Display for DfaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DfaError::TooManyStates { limit } => {
+                write!(f, "the dfa would need more than {} states", limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DfaError {}
+
+/// Converts a NFA into an equivalent dfa using the subset (powerset) construction, failing if the
+/// dfa would have more than `DEFAULT_STATE_LIMIT` states.
+///
+/// Capture slots are ignored: `Label::Save` transitions are treated as epsilon transitions.
+pub fn determinize(nfa: &Nfa) -> Result<Dfa, DfaError> {
+    determinize_with_limit(nfa, DEFAULT_STATE_LIMIT)
+}
+
+/// Same as `determinize`, but with a custom limit on the number of states.
+pub fn determinize_with_limit(nfa: &Nfa, state_limit: usize) -> Result<Dfa, DfaError> {
+    determinize_over(nfa, alphabet_classes(&[nfa]), state_limit)
+}
+
+/// Same as `determinize_with_limit`, but over the given character classes, which must be at least
+/// as fine as `alphabet_classes(&[nfa])`. This lets several dfas share one alphabet.
+pub fn determinize_over(
+    nfa: &Nfa,
+    classes: Vec<(char, char)>,
+    state_limit: usize,
+) -> Result<Dfa, DfaError> {
+    let mut builder = SubsetBuilder {
+        nfa,
+        state_limit,
+        state_ids: HashMap::new(),
+        unvisited: VecDeque::new(),
+        accepting: Vec::new(),
+    };
+
+    let initial_state = builder.state_for(HashSet::from([nfa.initial_state]))?;
+
+    // states are numbered in the order they are discovered, so visiting them first in first out
+    // pushes the rows of the table in order
+    let mut transitions = Vec::new();
+    while let Some(set) = builder.unvisited.pop_front() {
+        for (class_start, _) in classes.iter() {
+            let mut next_set: HashSet<State> = set.iter().copied().collect();
+            do_transition(&mut next_set, nfa, *class_start);
+            transitions.push(builder.state_for(next_set)?);
+        }
+    }
+
+    Ok(Dfa {
+        initial_state,
+        accepting: builder.accepting,
+        classes,
+        transitions,
+    })
+}
+
+/// Assigns dfa states to sets of nfa states during the subset construction.
+struct SubsetBuilder<'a> {
+    nfa: &'a Nfa,
+    state_limit: usize,
+    /// Every set is the sorted vec of an epsilon closed set of nfa states.
+    state_ids: HashMap<Vec<State>, State>,
+    /// The sets which were assigned a state but whose transitions were not computed yet.
+    unvisited: VecDeque<Vec<State>>,
+    accepting: Vec<bool>,
+}
+
+impl<'a> SubsetBuilder<'a> {
+    /// Returns the dfa state of the epsilon closure of `states`, creating it if necessary.
+    fn state_for(&mut self, mut states: HashSet<State>) -> Result<State, DfaError> {
+        do_epsilon_closure(&mut states, self.nfa);
+        let mut set: Vec<State> = states.into_iter().collect();
+        set.sort_unstable();
+
+        if let Some(id) = self.state_ids.get(&set) {
+            return Ok(*id);
+        }
+        let id = self.state_ids.len();
+        if id >= self.state_limit {
+            return Err(DfaError::TooManyStates {
+                limit: self.state_limit,
+            });
+        }
+        self.accepting.push(set.contains(&self.nfa.accepting_state));
+        self.state_ids.insert(set.clone(), id);
+        self.unvisited.push_back(set);
+        Ok(id)
+    }
+}
+
+/// Splits all of `char` into the coarsest ranges of consecutive characters which no transition
+/// of the given NFAs tells apart.
+pub fn alphabet_classes(nfas: &[&Nfa]) -> Vec<(char, char)> {
+    let mut class_starts = vec!['\0'];
+    for nfa in nfas {
+        for (label, _) in nfa.transitions.values().flatten() {
+            let ranges = match label {
+                Label::Epsilon | Label::Save(_) => continue,
+                Label::Char(c) => vec![(*c, *c)],
+                Label::Class(ranges) => ranges.clone(),
+            };
+            for (start, end) in ranges {
+                class_starts.push(start);
+                class_starts.extend(next_char(end));
+            }
+        }
+    }
+    class_starts.sort_unstable();
+    class_starts.dedup();
+
+    let class_ends = class_starts
+        .iter()
+        .skip(1)
+        .map(|start| prev_char(*start).unwrap());
+    class_starts
+        .iter()
+        .copied()
+        .zip(class_ends.chain(std::iter::once(char::MAX)))
+        .collect()
+}
+
+/// Runs a dfa on an input string.
+/// returns true if the dfa accepts the input string, and false otherwise.
+pub fn run_dfa(dfa: &Dfa, input_string: &str) -> bool {
+    let mut state = dfa.initial_state;
+    for c in input_string.chars() {
+        state = dfa.next_state(state, c);
+    }
+    dfa.accepting[state]
+}
+
+/// Returns true if some prefix of the input string is accepted by the dfa.
+///
+/// For a dfa built from `unanchored_nfa(m)` this is true exactly when m accepts some substring of
+/// the input string, like `search_nfa`.
+pub fn search_dfa(dfa: &Dfa, input_string: &str) -> bool {
+    let mut state = dfa.initial_state;
+    if dfa.accepting[state] {
+        return true;
+    }
+    for c in input_string.chars() {
+        state = dfa.next_state(state, c);
+        if dfa.accepting[state] {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns the minimal dfa accepting the same language as `dfa`, in a canonical form: two dfas
+/// accepting the same language always minimize to equal (`==`) dfas.
+///
+/// Unreachable states are dropped, equivalent states are merged by Hopcroft's partition refinement,
+/// adjacent character classes which every state treats alike are merged, and finally the states
+/// are numbered in breadth first order from the initial state, following the classes in order.
+pub fn minimize(dfa: &Dfa) -> Dfa {
+    let num_classes = dfa.classes.len();
+    let reachable = reachable_states(dfa);
+
+    // the predecessors of every state on every class, restricted to reachable states
+    let mut predecessors: Vec<Vec<Vec<State>>> =
+        vec![vec![Vec::new(); num_classes]; dfa.num_states()];
+    for &q in reachable.iter() {
+        let row = &dfa.transitions[q * num_classes..(q + 1) * num_classes];
+        for (class, next) in row.iter().enumerate() {
+            predecessors[*next][class].push(q);
+        }
+    }
+
+    // start from the partition into accepting and non-accepting states
+    let (accepting, rejecting): (Vec<State>, Vec<State>) =
+        reachable.iter().partition(|q| dfa.accepting[**q]);
+    let mut blocks: Vec<Vec<State>> = vec![accepting, rejecting];
+    blocks.retain(|block| !block.is_empty());
+    let mut block_of = vec![usize::MAX; dfa.num_states()];
+    for (b, block) in blocks.iter().enumerate() {
+        for q in block {
+            block_of[*q] = b;
+        }
+    }
+
+    // the worklist holds (splitter block, class) pairs. Initially one of the two blocks suffices.
+    let mut worklist: Vec<(usize, usize)> = (0..num_classes).map(|class| (0, class)).collect();
+    let mut in_worklist: HashSet<(usize, usize)> = worklist.iter().copied().collect();
+
+    while let Some((splitter, class)) = worklist.pop() {
+        in_worklist.remove(&(splitter, class));
+
+        // every state with a transition on `class` into the splitter block, grouped by block
+        let mut touched: HashMap<usize, Vec<State>> = HashMap::new();
+        for q in blocks[splitter].iter() {
+            for p in predecessors[*q][class].iter() {
+                touched.entry(block_of[*p]).or_default().push(*p);
+            }
+        }
+
+        let mut touched: Vec<(usize, Vec<State>)> = touched.into_iter().collect();
+        touched.sort_unstable();
+        for (b, mut inside) in touched {
+            inside.sort_unstable();
+            inside.dedup();
+            if inside.len() == blocks[b].len() {
+                continue;
+            }
+
+            // split block b into the states inside (which get a new block) and outside
+            let new_b = blocks.len();
+            for q in inside.iter() {
+                block_of[*q] = new_b;
+            }
+            blocks[b].retain(|q| block_of[*q] == b);
+            blocks.push(inside);
+
+            for class in 0..num_classes {
+                let smaller = if blocks[b].len() <= blocks[new_b].len() {
+                    b
+                } else {
+                    new_b
+                };
+                let to_add = if in_worklist.contains(&(b, class)) {
+                    new_b
+                } else {
+                    smaller
+                };
+                if in_worklist.insert((to_add, class)) {
+                    worklist.push((to_add, class));
+                }
+            }
+        }
+    }
+
+    // build the quotient dfa, then merge the classes it no longer tells apart
+    let quotient_transitions: Vec<State> = blocks
+        .iter()
+        .flat_map(|block| {
+            let q = block[0];
+            (0..num_classes).map(move |class| dfa.transitions[q * num_classes + class])
+        })
+        .map(|next| block_of[next])
+        .collect();
+    let quotient = merge_classes(&Dfa {
+        initial_state: block_of[dfa.initial_state],
+        accepting: blocks.iter().map(|block| dfa.accepting[block[0]]).collect(),
+        classes: dfa.classes.clone(),
+        transitions: quotient_transitions,
+    });
+
+    renumber_breadth_first(&quotient)
+}
+
+/// Returns the states reachable from the initial state of a dfa, in increasing order.
+fn reachable_states(dfa: &Dfa) -> Vec<State> {
+    let num_classes = dfa.classes.len();
+    let mut seen = vec![false; dfa.num_states()];
+    let mut stack = vec![dfa.initial_state];
+    seen[dfa.initial_state] = true;
+    while let Some(q) = stack.pop() {
+        for next in dfa.transitions[q * num_classes..(q + 1) * num_classes].iter() {
+            if !seen[*next] {
+                seen[*next] = true;
+                stack.push(*next);
+            }
+        }
+    }
+    (0..dfa.num_states()).filter(|q| seen[*q]).collect()
+}
+
+/// Merges every run of adjacent character classes on which all states have the same transition.
+fn merge_classes(dfa: &Dfa) -> Dfa {
+    let num_classes = dfa.classes.len();
+    let column = |class: usize| -> Vec<State> {
+        (0..dfa.num_states())
+            .map(|q| dfa.transitions[q * num_classes + class])
+            .collect()
+    };
+
+    let mut kept_classes: Vec<usize> = Vec::new();
+    let mut classes: Vec<(char, char)> = Vec::new();
+    for class in 0..num_classes {
+        match kept_classes.last() {
+            Some(last) if column(*last) == column(class) => {
+                classes.last_mut().unwrap().1 = dfa.classes[class].1;
+            }
+            _ => {
+                kept_classes.push(class);
+                classes.push(dfa.classes[class]);
+            }
+        }
+    }
+
+    let transitions = (0..dfa.num_states())
+        .flat_map(|q| {
+            kept_classes
+                .iter()
+                .map(move |class| dfa.transitions[q * num_classes + class])
+        })
+        .collect();
+    Dfa {
+        initial_state: dfa.initial_state,
+        accepting: dfa.accepting.clone(),
+        classes,
+        transitions,
+    }
+}
+
+/// Renumbers the states reachable from the initial state in breadth first order, following the
+/// transitions of each state in the order of the classes. Unreachable states are dropped.
+fn renumber_breadth_first(dfa: &Dfa) -> Dfa {
+    let num_classes = dfa.classes.len();
+    let mut new_number: Vec<Option<State>> = vec![None; dfa.num_states()];
+    let mut order: Vec<State> = vec![dfa.initial_state];
+    new_number[dfa.initial_state] = Some(0);
+
+    let mut i = 0;
+    while i < order.len() {
+        let q = order[i];
+        for next in dfa.transitions[q * num_classes..(q + 1) * num_classes].iter() {
+            if new_number[*next].is_none() {
+                new_number[*next] = Some(order.len());
+                order.push(*next);
+            }
+        }
+        i += 1;
+    }
+
+    Dfa {
+        initial_state: 0,
+        accepting: order.iter().map(|q| dfa.accepting[*q]).collect(),
+        classes: dfa.classes.clone(),
+        transitions: order
+            .iter()
+            .flat_map(|q| dfa.transitions[q * num_classes..(q + 1) * num_classes].iter())
+            .map(|next| new_number[*next].unwrap())
+            .collect(),
+    }
+}
+
+/// Converts a dfa into an equivalent NFA, e.g. to write it to a file with `write_nfa_to_file`.
+///
+/// States from which no accepting state can be reached are left out, and all the classes leading
+/// from one state to another become a single transition. Since a NFA has a single accepting
+/// state, every accepting state of the dfa gets an epsilon transition to a new accepting state.
+/// The initial state is also a new state, with an epsilon transition to the dfa's initial state,
+/// so the result can be combined with other NFAs by the constructions of `convert_regex_to_nfa`.
+pub fn dfa_to_nfa(dfa: &Dfa) -> Nfa {
+    let num_classes = dfa.classes.len();
+    let live = live_states(dfa);
+    let new_init_state = dfa.num_states();
+    let new_acc_state = dfa.num_states() + 1;
+
+    let mut transitions: HashMap<State, Vec<(Label, State)>> = HashMap::new();
+    if live[dfa.initial_state] {
+        transitions.insert(new_init_state, vec![(Label::Epsilon, dfa.initial_state)]);
+    }
+    for q in (0..dfa.num_states()).filter(|q| live[*q]) {
+        // the ranges leading to each target, with targets in order of their first class
+        let mut targets: Vec<(State, Vec<(char, char)>)> = Vec::new();
+        for class in 0..num_classes {
+            let next = dfa.transitions[q * num_classes + class];
+            if !live[next] {
+                continue;
+            }
+            match targets.iter_mut().find(|(target, _)| *target == next) {
+                Some((_, ranges)) => ranges.push(dfa.classes[class]),
+                None => targets.push((next, vec![dfa.classes[class]])),
+            }
+        }
+
+        let mut out_transitions: Vec<(Label, State)> = targets
+            .into_iter()
+            .map(|(next, ranges)| {
+                let ranges = normalize_ranges(ranges);
+                let label = match ranges[..] {
+                    [(start, end)] if start == end => Label::Char(start),
+                    _ => Label::Class(ranges),
+                };
+                (label, next)
+            })
+            .collect();
+        if dfa.accepting[q] {
+            out_transitions.push((Label::Epsilon, new_acc_state));
+        }
+        if !out_transitions.is_empty() {
+            transitions.insert(q, out_transitions);
+        }
+    }
+
+    Nfa {
+        initial_state: new_init_state,
+        accepting_state: new_acc_state,
+        transitions,
+    }
+}
+
+/// Returns, for every state of a dfa, whether an accepting state can be reached from it.
+pub fn live_states(dfa: &Dfa) -> Vec<bool> {
+    let num_classes = dfa.classes.len();
+    let mut predecessors: Vec<Vec<State>> = vec![Vec::new(); dfa.num_states()];
+    for q in 0..dfa.num_states() {
+        for next in dfa.transitions[q * num_classes..(q + 1) * num_classes].iter() {
+            predecessors[*next].push(q);
+        }
+    }
+
+    let mut live = dfa.accepting.clone();
+    let mut stack: Vec<State> = (0..dfa.num_states()).filter(|q| live[*q]).collect();
+    while let Some(q) = stack.pop() {
+        for p in predecessors[q].iter() {
+            if !live[*p] {
+                live[*p] = true;
+                stack.push(*p);
+            }
+        }
+    }
+    live
+}
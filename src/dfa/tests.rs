@@ -0,0 +1,170 @@
+#![cfg(test)]
+
+use super::{
+    alphabet_classes, determinize, determinize_with_limit, dfa_to_nfa, minimize, run_dfa,
+    search_dfa, DfaError,
+};
+use crate::{
+    stage_1::parse_regex,
+    stage_2::{convert_regex_to_nfa, unanchored_nfa},
+    stage_3::{run_nfa, search_nfa},
+    Label,
+};
+
+#[test]
+fn test_alphabet_classes() {
+    let m = convert_regex_to_nfa(&parse_regex("a[c-e]|[^x]").unwrap());
+    assert_eq!(
+        alphabet_classes(&[&m]),
+        vec![
+            ('\0', '`'),
+            ('a', 'a'),
+            ('b', 'b'),
+            ('c', 'e'),
+            ('f', 'w'),
+            ('x', 'x'),
+            ('y', char::MAX)
+        ]
+    );
+}
+
+#[test]
+fn test_determinize() {
+    let m = convert_regex_to_nfa(&parse_regex("(a|b)*abb").unwrap());
+    let dfa = determinize(&m).unwrap();
+
+    // the five subsets of the textbook construction plus the dead state for any other character
+    assert_eq!(dfa.num_states(), 6);
+    assert_eq!(dfa.classes.len(), 4);
+
+    assert!(run_dfa(&dfa, "abb"));
+    assert!(run_dfa(&dfa, "babaabb"));
+    assert!(!run_dfa(&dfa, ""));
+    assert!(!run_dfa(&dfa, "abba"));
+    assert!(!run_dfa(&dfa, "abcabb"));
+}
+
+#[test]
+fn test_dfa_agrees_with_nfa() {
+    let patterns = [
+        "a",
+        "(a*)|(bc)(d*)",
+        "[a-z_][a-z0-9_]*",
+        "\\d{2,4}-?x+",
+        ".a.",
+        "(?:ab|a)(?:bc|c)",
+        "[^aeiou]*",
+    ];
+    let inputs = [
+        "", "a", "bcdd", "abc", "snake_9", "9snake", "12-x", "12345x", "1234xx", "xay", "a\na",
+        "rhythm", "vowel",
+    ];
+    for pattern in patterns {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        let dfa = determinize(&m).unwrap();
+        let search_dfa_m = determinize(&unanchored_nfa(&m)).unwrap();
+        for input in inputs {
+            assert_eq!(
+                run_dfa(&dfa, input),
+                run_nfa(&m, input),
+                "{} on {:?}",
+                pattern,
+                input
+            );
+            assert_eq!(
+                search_dfa(&search_dfa_m, input),
+                search_nfa(&m, input),
+                "{} searched in {:?}",
+                pattern,
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_determinize_state_limit() {
+    // the dfa of (a|b)*a(a|b){n} needs 2^(n+1) states
+    let m = convert_regex_to_nfa(&parse_regex("(a|b)*a(a|b){5}").unwrap());
+    assert_eq!(
+        determinize_with_limit(&m, 50).err(),
+        Some(DfaError::TooManyStates { limit: 50 })
+    );
+    let dfa = determinize(&m).unwrap();
+    assert!(dfa.num_states() > 64);
+    assert!(run_dfa(&dfa, "bbbabbbaa"));
+    assert!(!run_dfa(&dfa, "abbbbbbbaa"));
+}
+
+#[test]
+fn test_minimize() {
+    let m = convert_regex_to_nfa(&parse_regex("(a|b)*abb").unwrap());
+    let dfa = minimize(&determinize(&m).unwrap());
+
+    // the four states of the textbook minimal dfa plus the dead state
+    assert_eq!(dfa.num_states(), 5);
+    assert_eq!(dfa.initial_state, 0);
+    assert!(run_dfa(&dfa, "babaabb"));
+    assert!(!run_dfa(&dfa, "abba"));
+    assert!(!run_dfa(&dfa, "abcabb"));
+}
+
+#[test]
+fn test_minimize_is_canonical() {
+    let equivalent_patterns = [
+        ["a|b|c", "[a-c]", "[abc]|b"],
+        ["(a|b)*", "(a*b*)*", "(?:b|a)*|a"],
+        ["a+", "aa*", "a*a"],
+        ["(ab)*a", "a(ba)*", "a(?:ba){0,}"],
+        ["x{2,3}", "xx|xxx", "xxx?"],
+    ];
+    for patterns in equivalent_patterns {
+        let minimized: Vec<_> = patterns
+            .iter()
+            .map(|pattern| {
+                let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+                minimize(&determinize(&m).unwrap())
+            })
+            .collect();
+        for (pattern, dfa) in patterns.iter().zip(minimized.iter()) {
+            assert_eq!(dfa, &minimized[0], "{} and {}", pattern, patterns[0]);
+        }
+    }
+
+    let a = convert_regex_to_nfa(&parse_regex("a*").unwrap());
+    let b = convert_regex_to_nfa(&parse_regex("a+").unwrap());
+    assert_ne!(
+        minimize(&determinize(&a).unwrap()),
+        minimize(&determinize(&b).unwrap())
+    );
+}
+
+#[test]
+fn test_dfa_to_nfa() {
+    for pattern in ["(a|b)*abb", "[a-c]x|[b-d]y", "\\d+(\\.\\d+)?", "z{0}"] {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        let dfa = minimize(&determinize(&m).unwrap());
+        let converted = dfa_to_nfa(&dfa);
+        for input in [
+            "", "abb", "babb", "ax", "cx", "dy", "ay", "3.14", "3.", "42",
+        ] {
+            assert_eq!(
+                run_nfa(&converted, input),
+                run_nfa(&m, input),
+                "{} on {:?}",
+                pattern,
+                input
+            );
+        }
+    }
+
+    // the dead state (state 1, reached on the class before 'a') is left out, and parallel
+    // classes become a single transition
+    let m = convert_regex_to_nfa(&parse_regex("[a-c]|[x-z]").unwrap());
+    let converted = dfa_to_nfa(&minimize(&determinize(&m).unwrap()));
+    assert!(!converted.transitions.contains_key(&1));
+    assert_eq!(
+        converted.transitions[&0],
+        vec![(Label::Class(vec![('a', 'c'), ('x', 'z')]), 2)]
+    );
+}
@@ -0,0 +1,342 @@
+mod tests;
+
+use crate::{
+    char_class::next_char,
+    dfa::{determinize, minimize, Dfa, DfaError},
+    Nfa, State,
+};
+
+/// Returns an iterator over every string of at most `max_len` characters accepted by a NFA, in
+/// shortlex order: shorter strings first, and strings of the same length in lexicographic order
+/// of their characters.
+///
+/// The NFA is determinized (failing if its dfa would have more than `DEFAULT_STATE_LIMIT`
+/// states) so every string is produced exactly once. Strings are produced lazily, so even a
+/// language with astronomically many short strings, such as that of ".{3}", can be enumerated up
+/// to a limit with `take`. Nothing is computed for lengths beyond the longest string produced, so
+/// `max_len` may be as large as wanted.
+pub fn shortlex_strings(m: &Nfa, max_len: usize) -> Result<ShortlexIter, DfaError> {
+    let dfa = minimize(&determinize(m)?);
+    let can_finish = vec![dfa.accepting.clone()];
+    Ok(ShortlexIter {
+        dfa,
+        can_finish,
+        max_len,
+        len: 0,
+        fresh: true,
+        path: Vec::new(),
+    })
+}
+
+pub struct ShortlexIter {
+    dfa: Dfa,
+    /// `can_finish[k][q]` is true if the dfa accepts some string of exactly k characters starting
+    /// from state q. It is extended one length at a time, up to `len`.
+    can_finish: Vec<Vec<bool>>,
+    max_len: usize,
+    /// The length of the strings currently being enumerated.
+    len: usize,
+    /// True if no string of length `len` was produced yet.
+    fresh: bool,
+    /// The characters of the last string produced, each with the state it was read from and the
+    /// index of its class.
+    path: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    from: State,
+    class: usize,
+    c: char,
+}
+
+impl ShortlexIter {
+    /// Extends `can_finish` by the next length, and returns false if no state accepts a string of
+    /// that length, in which case no state accepts any longer string either.
+    fn extend_can_finish(&mut self) -> bool {
+        let num_classes = self.dfa.classes.len();
+        let previous = self.can_finish.last().unwrap();
+        let current: Vec<bool> = (0..self.dfa.num_states())
+            .map(|q| {
+                self.dfa.transitions[q * num_classes..(q + 1) * num_classes]
+                    .iter()
+                    .any(|next| previous[*next])
+            })
+            .collect();
+        let any = current.contains(&true);
+        self.can_finish.push(current);
+        any
+    }
+
+    /// The state reached after reading the whole path.
+    fn current_state(&self) -> State {
+        match self.path.last() {
+            Some(step) => self.next_state(step.from, step.class),
+            None => self.dfa.initial_state,
+        }
+    }
+
+    fn next_state(&self, q: State, class: usize) -> State {
+        self.dfa.transitions[q * self.dfa.classes.len() + class]
+    }
+
+    /// Returns the first class, starting from class `first`, leading from state q to a state which
+    /// accepts some string of exactly `remaining` characters.
+    fn next_class(&self, q: State, first: usize, remaining: usize) -> Option<usize> {
+        (first..self.dfa.classes.len())
+            .find(|class| self.can_finish[remaining][self.next_state(q, *class)])
+    }
+
+    /// Extends the path with the smallest characters leading to a string of length `len`.
+    /// Every state on the way must be able to finish, so this never gets stuck.
+    fn descend(&mut self) {
+        while self.path.len() < self.len {
+            let from = self.current_state();
+            let remaining = self.len - self.path.len() - 1;
+            let class = self.next_class(from, 0, remaining).unwrap();
+            self.path.push(Step {
+                from,
+                class,
+                c: self.dfa.classes[class].0,
+            });
+        }
+    }
+
+    /// Replaces the path by the next one of the same length in lexicographic order. Returns false
+    /// if the path was the last one.
+    fn advance(&mut self) -> bool {
+        while let Some(step) = self.path.pop() {
+            let remaining = self.len - self.path.len() - 1;
+
+            // every character of a class leads to the same state, so the rest of the class is
+            // just as good as the character it replaces
+            let next_in_class = next_char(step.c).filter(|c| *c <= self.dfa.classes[step.class].1);
+            let next_step = match next_in_class {
+                Some(c) => Some(Step { c, ..step }),
+                None => self
+                    .next_class(step.from, step.class + 1, remaining)
+                    .map(|class| Step {
+                        from: step.from,
+                        class,
+                        c: self.dfa.classes[class].0,
+                    }),
+            };
+            if let Some(next_step) = next_step {
+                self.path.push(next_step);
+                self.descend();
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for ShortlexIter {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while self.len <= self.max_len {
+            let found = if self.fresh {
+                self.fresh = false;
+                let can_finish = self.can_finish[self.len][self.dfa.initial_state];
+                if can_finish {
+                    self.descend();
+                }
+                can_finish
+            } else {
+                self.advance()
+            };
+            if found {
+                return Some(self.path.iter().map(|step| step.c).collect());
+            }
+
+            self.len += 1;
+            self.fresh = true;
+            self.path.clear();
+            if self.len <= self.max_len && !self.extend_can_finish() {
+                break;
+            }
+        }
+        None
+    }
+}
+
+/// Returns the number of strings of exactly `len` characters accepted by a NFA, saturating at
+/// `u128::MAX`.
+///
+/// The NFA is determinized (failing if its dfa would have more than `DEFAULT_STATE_LIMIT`
+/// states), so that every accepted string has exactly one run and counting runs counts strings.
+/// Comparing the counts of two patterns for the same length tells which one is stricter.
+pub fn count_strings(m: &Nfa, len: usize) -> Result<u128, DfaError> {
+    let dfa = minimize(&determinize(m)?);
+    let counts = string_counts(&dfa, len);
+    Ok(counts[len][dfa.initial_state])
+}
+
+/// Returns `counts` such that `counts[k][q]` is the number of strings of exactly k characters the
+/// dfa accepts starting from state q, for every k up to `len`, saturating at `u128::MAX`.
+fn string_counts(dfa: &Dfa, len: usize) -> Vec<Vec<u128>> {
+    let num_classes = dfa.classes.len();
+    let mut counts: Vec<Vec<u128>> = vec![dfa.accepting.iter().map(|a| *a as u128).collect()];
+    for k in 1..=len {
+        let previous = &counts[k - 1];
+        let current = (0..dfa.num_states())
+            .map(|q| {
+                (0..num_classes).fold(0u128, |total, class| {
+                    let next = dfa.transitions[q * num_classes + class];
+                    let strings = class_size(dfa.classes[class]).saturating_mul(previous[next]);
+                    total.saturating_add(strings)
+                })
+            })
+            .collect();
+        counts.push(current);
+    }
+    counts
+}
+
+/// The number of `char`s in an inclusive range, leaving out the surrogate code points.
+fn class_size((start, end): (char, char)) -> u128 {
+    let size = end as u128 - start as u128 + 1;
+    if start <= '\u{D7FF}' && end >= '\u{E000}' {
+        size - 0x800
+    } else {
+        size
+    }
+}
+
+/// Returns the character `index` places after `start`, skipping over the surrogate code points.
+fn nth_char(start: char, index: u128) -> char {
+    let mut code = start as u128 + index;
+    if start <= '\u{D7FF}' && code > 0xD7FF {
+        code += 0x800;
+    }
+    char::from_u32(code as u32).unwrap()
+}
+
+/// An endless iterator over uniformly random strings of a given length accepted by a NFA, drawn
+/// from a seeded pseudo random generator so every run with the same seed draws the same strings.
+///
+/// Every accepted string of the length is equally likely. The iterator is empty if no string of
+/// the length is accepted.
+pub struct Sampler {
+    dfa: Dfa,
+    counts: Vec<Vec<u128>>,
+    len: usize,
+    rng: SplitMix64,
+}
+
+/// An error produced while setting up a `Sampler`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleError {
+    /// The NFA could not be determinized.
+    Dfa(DfaError),
+    /// The number of accepted strings of the length saturates `count_strings`, so they cannot be
+    /// drawn uniformly.
+    TooManyStrings { len: usize },
+}
+
+impl std::fmt::Display for SampleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleError::Dfa(err) => write!(f, "{}", err),
+            SampleError::TooManyStrings { len } => {
+                write!(
+                    f,
+                    "too many strings of length {} to draw from uniformly",
+                    len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SampleError {}
+
+impl Sampler {
+    /// Determinizes the NFA, failing if its dfa would have more than `DEFAULT_STATE_LIMIT` states,
+    /// and counts its strings of the length, failing if their number saturates at `u128::MAX`.
+    pub fn new(m: &Nfa, len: usize, seed: u64) -> Result<Sampler, SampleError> {
+        let dfa = minimize(&determinize(m).map_err(SampleError::Dfa)?);
+        let counts = string_counts(&dfa, len);
+        // a count which saturates also saturates every count it is added to, so checking the
+        // total is enough to know that every weight drawn from below is exact
+        if counts[len][dfa.initial_state] == u128::MAX {
+            return Err(SampleError::TooManyStrings { len });
+        }
+        Ok(Sampler {
+            dfa,
+            counts,
+            len,
+            rng: SplitMix64 { state: seed },
+        })
+    }
+}
+
+impl Iterator for Sampler {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.counts[self.len][self.dfa.initial_state] == 0 {
+            return None;
+        }
+
+        // each character is drawn with a probability proportional to the number of accepted
+        // strings which continue with it
+        let num_classes = self.dfa.classes.len();
+        let mut sample = String::new();
+        let mut q = self.dfa.initial_state;
+        for remaining in (0..self.len).rev() {
+            let row = &self.dfa.transitions[q * num_classes..(q + 1) * num_classes];
+            let weights: Vec<u128> = row
+                .iter()
+                .zip(self.dfa.classes.iter())
+                .map(|(next, class)| {
+                    class_size(*class).saturating_mul(self.counts[remaining][*next])
+                })
+                .collect();
+            let total = weights
+                .iter()
+                .fold(0u128, |total, w| total.saturating_add(*w));
+
+            let mut r = self.rng.below(total);
+            for (class, weight) in weights.iter().enumerate() {
+                if r < *weight {
+                    let next = row[class];
+                    let c = nth_char(self.dfa.classes[class].0, r / self.counts[remaining][next]);
+                    sample.push(c);
+                    q = next;
+                    break;
+                }
+                r -= weight;
+            }
+        }
+        Some(sample)
+    }
+}
+
+/// The SplitMix64 pseudo random generator, which is small, fast and good enough for fuzzing.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly random number below `bound`, which must not be 0.
+    fn below(&mut self, bound: u128) -> u128 {
+        // rejecting the numbers in the incomplete last multiple of bound keeps the result uniform
+        let zone = u128::MAX - u128::MAX % bound;
+        loop {
+            let r = ((self.next_u64() as u128) << 64) | self.next_u64() as u128;
+            if r < zone {
+                return r % bound;
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+#![cfg(test)]
+
+use super::{count_strings, shortlex_strings, SampleError, Sampler};
+use std::collections::HashMap;
+
+use crate::{stage_1::parse_regex, stage_2::convert_regex_to_nfa, stage_3::run_nfa, Nfa};
+
+fn nfa(pattern: &str) -> Nfa {
+    convert_regex_to_nfa(&parse_regex(pattern).unwrap())
+}
+
+fn enumerate(pattern: &str, max_len: usize) -> Vec<String> {
+    shortlex_strings(&nfa(pattern), max_len).unwrap().collect()
+}
+
+#[test]
+fn test_shortlex_strings() {
+    assert_eq!(
+        enumerate("b|a|ab|ba|a{0}", 5),
+        vec!["", "a", "b", "ab", "ba"]
+    );
+    assert_eq!(
+        enumerate("(a|b)*", 2),
+        vec!["", "a", "b", "aa", "ab", "ba", "bb"]
+    );
+    assert_eq!(enumerate("a+b", 4), vec!["ab", "aab", "aaab"]);
+    assert_eq!(enumerate("x{2,3}", 1), Vec::<String>::new());
+    assert_eq!(
+        enumerate("[0-2][a-b]", 3),
+        vec!["0a", "0b", "1a", "1b", "2a", "2b"]
+    );
+    assert_eq!(enumerate("a&b", 5), Vec::<String>::new());
+
+    // equivalent patterns enumerate the same strings, each exactly once
+    assert_eq!(
+        enumerate("(a|ab)(c|bcd)", 5),
+        enumerate("abcd?|ac|abbcd", 5)
+    );
+    assert_eq!(enumerate("(a|a)*", 3), vec!["", "a", "aa", "aaa"]);
+
+    // the lengths are only looked at up to the longest string there is
+    assert_eq!(enumerate("b|a{3}", usize::MAX), vec!["b", "aaa"]);
+    let first: Vec<String> = shortlex_strings(&nfa("(?:ab)*"), usize::MAX)
+        .unwrap()
+        .take(3)
+        .collect();
+    assert_eq!(first, vec!["", "ab", "abab"]);
+}
+
+#[test]
+fn test_shortlex_strings_order() {
+    let m = nfa("[a-c]*d|\\d{2}");
+    let strings: Vec<String> = shortlex_strings(&m, 4).unwrap().collect();
+    for pair in strings.windows(2) {
+        let (s1, s2) = (&pair[0], &pair[1]);
+        assert!(
+            (s1.chars().count(), s1) < (s2.chars().count(), s2),
+            "{:?} before {:?}",
+            s1,
+            s2
+        );
+    }
+    assert!(strings.iter().all(|s| run_nfa(&m, s)));
+    // d, 100 pairs of digits, 3 + 9 + 27 strings ending in d
+    assert_eq!(strings.len(), 1 + 100 + 3 + 9 + 27);
+}
+
+#[test]
+fn test_shortlex_strings_huge_language() {
+    // there are over a million single characters, so only the first few are ever produced
+    let first: Vec<String> = shortlex_strings(&nfa(".{3}"), 3).unwrap().take(3).collect();
+    assert_eq!(first, vec!["\0\0\0", "\0\0\u{1}", "\0\0\u{2}"]);
+
+    let first: Vec<String> = shortlex_strings(&nfa("[^\\n]+x"), 10)
+        .unwrap()
+        .skip(1)
+        .take(2)
+        .collect();
+    assert_eq!(first, vec!["\u{1}x", "\u{2}x"]);
+}
+
+#[test]
+fn test_count_strings() {
+    let count = |pattern: &str, len: usize| count_strings(&nfa(pattern), len).unwrap();
+
+    assert_eq!(count("(a|b)*", 10), 1024);
+    assert_eq!(count("(a|b)*abb", 5), 4);
+    assert_eq!(count("a*|aa", 2), 1);
+    assert_eq!(count("\\d{3}-\\d{4}", 8), 10_000_000);
+    assert_eq!(count("x{2,3}", 1), 0);
+    assert_eq!(count("a&b", 1), 0);
+
+    // every char but the 2048 surrogates, except newline for '.'
+    assert_eq!(count(".", 1), 0x110000 - 0x800 - 1);
+    assert_eq!(count(".{2}", 2), (0x110000u128 - 0x800 - 1).pow(2));
+    assert_eq!(count(".*", 7), u128::MAX);
+
+    // the counts agree with the enumeration
+    for pattern in ["(ab|a)(bc|c)*", "[a-c]*d|[0-3]{2}", "~(a*)&[ab]*"] {
+        for len in 0..6 {
+            let enumerated = shortlex_strings(&nfa(pattern), len)
+                .unwrap()
+                .filter(|s| s.chars().count() == len)
+                .count();
+            assert_eq!(
+                count(pattern, len),
+                enumerated as u128,
+                "{} {}",
+                pattern,
+                len
+            );
+        }
+    }
+}
+
+#[test]
+fn test_sampler() {
+    let m = nfa("[a-c]{2}x|d[\\d]y");
+    let samples: Vec<String> = Sampler::new(&m, 3, 42).unwrap().take(3000).collect();
+    assert!(samples.iter().all(|s| run_nfa(&m, s)));
+
+    // there are 9 + 10 strings of length 3, each drawn about 3000 / 19 = 158 times
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for sample in samples.iter() {
+        *frequencies.entry(sample).or_default() += 1;
+    }
+    assert_eq!(frequencies.len(), 19);
+    assert!(
+        frequencies.values().all(|n| (100..220).contains(n)),
+        "{:?}",
+        frequencies
+    );
+
+    // the same seed draws the same strings
+    let again: Vec<String> = Sampler::new(&m, 3, 42).unwrap().take(3000).collect();
+    assert_eq!(samples, again);
+    let other: Vec<String> = Sampler::new(&m, 3, 7).unwrap().take(3000).collect();
+    assert_ne!(samples, other);
+
+    assert_eq!(Sampler::new(&m, 4, 42).unwrap().next(), None);
+    let any: Vec<String> = Sampler::new(&nfa(".+"), 5, 1).unwrap().take(10).collect();
+    assert!(any.iter().all(|s| s.chars().count() == 5));
+
+    // there are more strings of 7 characters than a u128 can count
+    assert_eq!(
+        Sampler::new(&nfa(".*"), 7, 1).err(),
+        Some(SampleError::TooManyStrings { len: 7 })
+    );
+}
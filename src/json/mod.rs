@@ -0,0 +1,721 @@
+mod tests;
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use crate::{
+    char_class::normalize_ranges,
+    stage_1::MAX_NESTING_DEPTH,
+    stage_2::{state_count_bound, MAX_NFA_STATES, MAX_REPETITION},
+    stage_3::Captures,
+    CharClass, Label, Nfa, RegexExpr, State,
+};
+
+/// The deepest a JSON value may be nested. This leaves room for the json of any expression the
+/// parser accepts, inside a few more values, while keeping the recursive parser within the stack.
+pub const MAX_JSON_DEPTH: usize = MAX_NESTING_DEPTH + 8;
+
+/// A JSON value. The members of an object keep their order, so that serializing a value always
+/// produces the same text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// An error produced while reading JSON, either because the text is not JSON at all or because
+/// the value does not follow the schema described on `expr_to_json`, `nfa_to_json` or
+/// `captures_to_json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// The text is not valid JSON, starting at the given byte offset.
+    Syntax { offset: usize },
+    /// The value starting at the given byte offset is nested more than `MAX_JSON_DEPTH` levels deep.
+    TooDeeplyNested { offset: usize },
+    /// An object lacks a member the schema requires.
+    MissingField { field: String },
+    /// A member, or the value itself if `field` is empty, is not what the schema expects.
+    InvalidValue {
+        field: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Syntax { offset } => write!(f, "invalid JSON at offset {}", offset),
+            JsonError::TooDeeplyNested { offset } => {
+                write!(f, "JSON at offset {} is nested too deeply", offset)
+            }
+            JsonError::MissingField { field } => write!(f, "missing field \"{}\"", field),
+            JsonError::InvalidValue { field, expected } if field.is_empty() => {
+                write!(f, "expected {}", expected)
+            }
+            JsonError::InvalidValue { field, expected } => {
+                write!(f, "field \"{}\" should be {}", field, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Serializes a value without any whitespace. Numbers which are integers are written without a
+/// fraction, and numbers JSON cannot represent (infinities and NaN) are written as null.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if !n.is_finite() => f.write_str("null"),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Parses a JSON text (RFC 8259) into a value, allowing whitespace around it.
+/// Values nested more than `MAX_JSON_DEPTH` levels deep are rejected.
+pub fn parse_json(input: &str) -> Result<Json, JsonError> {
+    let mut parser = JsonParser { input, pos: 0 };
+    let value = parser.parse_value(1)?;
+    parser.skip_whitespace();
+    if parser.pos < input.len() {
+        return Err(parser.error());
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    /// The byte offset of the next character to read.
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self) -> JsonError {
+        JsonError::Syntax { offset: self.pos }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `expected` after any whitespace, or fails without consuming anything else.
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Parses the value at the given depth, counting the whole text as depth 1.
+    fn parse_value(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        if depth > MAX_JSON_DEPTH {
+            return Err(JsonError::TooDeeplyNested { offset: self.pos });
+        }
+        match self.peek() {
+            Some('n') => self.parse_keyword("null", Json::Null),
+            Some('t') => self.parse_keyword("true", Json::Bool(true)),
+            Some('f') => self.parse_keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error()),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let name = self.parse_string()?;
+                    self.expect(':')?;
+                    members.push((name, self.parse_value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error()),
+                    }
+                }
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+        if self.input[self.pos..].starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        let digits = |parser: &mut JsonParser| {
+            let first = parser.pos;
+            while let Some('0'..='9') = parser.peek() {
+                parser.pos += 1;
+            }
+            parser.pos > first
+        };
+
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some('0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error());
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error());
+            }
+        }
+        if let Some('e' | 'E') = self.peek() {
+            self.pos += 1;
+            if let Some('+' | '-') = self.peek() {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error());
+            }
+        }
+        let number = self.input[start..self.pos].parse().unwrap();
+        Ok(Json::Number(number))
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        if self.peek() != Some('"') {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let escape_start = self.pos;
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape(escape_start)?,
+                        _ => {
+                            return Err(JsonError::Syntax {
+                                offset: escape_start,
+                            })
+                        }
+                    };
+                    s.push(c);
+                }
+                Some(c) if (c as u32) >= 0x20 => s.push(c),
+                _ => {
+                    return Err(JsonError::Syntax {
+                        offset: escape_start,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Reads the hexadecimal digits of a "\u" escape, and of the low surrogate following it if it
+    /// is a high surrogate.
+    fn parse_unicode_escape(&mut self, escape_start: usize) -> Result<char, JsonError> {
+        let error = JsonError::Syntax {
+            offset: escape_start,
+        };
+        let hex4 = |parser: &mut JsonParser| {
+            let hex = parser.input.get(parser.pos..parser.pos + 4)?;
+            // from_str_radix alone would also accept a sign
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            parser.pos += 4;
+            u32::from_str_radix(hex, 16).ok()
+        };
+
+        let high = hex4(self).ok_or(error.clone())?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or(error);
+        }
+        if !self.input[self.pos..].starts_with("\\u") {
+            return Err(error);
+        }
+        self.pos += 2;
+        let low = hex4(self).ok_or(error.clone())?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(error);
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).ok_or(error)
+    }
+}
+
+/// Builds an object from its members.
+fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        members
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+fn string(s: impl Into<String>) -> Json {
+    Json::String(s.into())
+}
+
+fn number(n: usize) -> Json {
+    Json::Number(n as f64)
+}
+
+fn ranges_to_json(ranges: &[(char, char)]) -> Json {
+    Json::Array(
+        ranges
+            .iter()
+            .map(|(start, end)| Json::Array(vec![string(*start), string(*end)]))
+            .collect(),
+    )
+}
+
+/// Serializes an expression as nested objects, each with a "type" member saying which kind of
+/// expression it is:
+///
+/// ```text
+/// {"type": "char", "char": "a"}
+/// {"type": "class", "ranges": [["a", "z"], ["_", "_"]], "negated": false}
+/// {"type": "any"}
+/// {"type": "star" | "plus" | "optional" | "not", "expr": <expression>}
+/// {"type": "repeat", "expr": <expression>, "min": 2, "max": 5 | null}
+/// {"type": "concat" | "or" | "and", "left": <expression>, "right": <expression>}
+/// {"type": "group", "expr": <expression>, "index": 1}
+/// ```
+///
+/// Characters are strings of exactly one character, and a "max" of null means unbounded.
+pub fn expr_to_json(expr: &RegexExpr) -> Json {
+    let unary =
+        |kind: &str, e: &RegexExpr| object(vec![("type", string(kind)), ("expr", expr_to_json(e))]);
+    let binary = |kind: &str, e1: &RegexExpr, e2: &RegexExpr| {
+        object(vec![
+            ("type", string(kind)),
+            ("left", expr_to_json(e1)),
+            ("right", expr_to_json(e2)),
+        ])
+    };
+    match expr {
+        RegexExpr::SingleChar(c) => object(vec![("type", string("char")), ("char", string(*c))]),
+        RegexExpr::Class(class) => object(vec![
+            ("type", string("class")),
+            ("ranges", ranges_to_json(&class.ranges)),
+            ("negated", Json::Bool(class.negated)),
+        ]),
+        RegexExpr::AnyChar => object(vec![("type", string("any"))]),
+        RegexExpr::Star(e) => unary("star", e),
+        RegexExpr::Plus(e) => unary("plus", e),
+        RegexExpr::Optional(e) => unary("optional", e),
+        RegexExpr::Not(e) => unary("not", e),
+        RegexExpr::Repeat(e, min, max) => object(vec![
+            ("type", string("repeat")),
+            ("expr", expr_to_json(e)),
+            ("min", Json::Number(*min as f64)),
+            (
+                "max",
+                max.map_or(Json::Null, |max| Json::Number(max as f64)),
+            ),
+        ]),
+        RegexExpr::Concat(e1, e2) => binary("concat", e1, e2),
+        RegexExpr::Or(e1, e2) => binary("or", e1, e2),
+        RegexExpr::And(e1, e2) => binary("and", e1, e2),
+        RegexExpr::Group(e, index) => object(vec![
+            ("type", string("group")),
+            ("expr", expr_to_json(e)),
+            ("index", number(*index)),
+        ]),
+    }
+}
+
+/// Reads back an expression in the schema described on `expr_to_json`. Ranges of a class may be
+/// given in any order, and are normalized. Like the parser, this rejects expressions nested more
+/// than `MAX_NESTING_DEPTH` levels deep, counts above `MAX_REPETITION`, expressions whose nfa could
+/// have more than `MAX_NFA_STATES` states, and a group index of 0, which stands for the whole match.
+pub fn expr_from_json(json: &Json) -> Result<RegexExpr, JsonError> {
+    let expr = expr_from_json_at(json, 1)?;
+    if state_count_bound(&expr) > MAX_NFA_STATES {
+        return Err(invalid("", "an expression whose nfa is not too large"));
+    }
+    Ok(expr)
+}
+
+/// Reads back an expression nested `depth` levels deep, counting the whole expression as depth 1.
+fn expr_from_json_at(json: &Json, depth: usize) -> Result<RegexExpr, JsonError> {
+    let sub_expr = |name: &str| {
+        if depth >= MAX_NESTING_DEPTH {
+            return Err(invalid(name, "an expression nested less deeply"));
+        }
+        Ok(Box::new(expr_from_json_at(field(json, name)?, depth + 1)?))
+    };
+    let expr = match as_str(field(json, "type")?, "type")? {
+        "char" => RegexExpr::SingleChar(as_char(field(json, "char")?, "char")?),
+        "class" => RegexExpr::Class(CharClass::new(
+            ranges_from_json(field(json, "ranges")?, "ranges")?,
+            as_bool(field(json, "negated")?, "negated")?,
+        )),
+        "any" => RegexExpr::AnyChar,
+        "star" => RegexExpr::Star(sub_expr("expr")?),
+        "plus" => RegexExpr::Plus(sub_expr("expr")?),
+        "optional" => RegexExpr::Optional(sub_expr("expr")?),
+        "not" => RegexExpr::Not(sub_expr("expr")?),
+        "repeat" => {
+            let min = as_u32(field(json, "min")?, "min")?;
+            let max = match field(json, "max")? {
+                Json::Null => None,
+                max => Some(as_u32(max, "max")?),
+            };
+            if min > MAX_REPETITION {
+                return Err(invalid("min", "at most 1000"));
+            }
+            if max.is_some_and(|max| max > MAX_REPETITION) {
+                return Err(invalid("max", "at most 1000"));
+            }
+            if max.is_some_and(|max| max < min) {
+                return Err(invalid("max", "at least min"));
+            }
+            let repeated = RegexExpr::Repeat(sub_expr("expr")?, min, max);
+            // checked here as well, since the operands of "and" and "not" are built on their own
+            if state_count_bound(&repeated) > MAX_NFA_STATES {
+                return Err(invalid(
+                    "expr",
+                    "small enough to be repeated that many times",
+                ));
+            }
+            repeated
+        }
+        "concat" => RegexExpr::Concat(sub_expr("left")?, sub_expr("right")?),
+        "or" => RegexExpr::Or(sub_expr("left")?, sub_expr("right")?),
+        "and" => RegexExpr::And(sub_expr("left")?, sub_expr("right")?),
+        "group" => match as_usize(field(json, "index")?, "index")? {
+            0 => return Err(invalid("index", "positive, as group 0 is the whole match")),
+            index => RegexExpr::Group(sub_expr("expr")?, index),
+        },
+        _ => return Err(invalid("type", "the name of a kind of expression")),
+    };
+    Ok(expr)
+}
+
+fn label_to_json(label: &Label) -> Json {
+    match label {
+        Label::Epsilon => object(vec![("type", string("epsilon"))]),
+        Label::Char(c) => object(vec![("type", string("char")), ("char", string(*c))]),
+        Label::Class(ranges) => object(vec![
+            ("type", string("class")),
+            ("ranges", ranges_to_json(ranges)),
+        ]),
+        Label::Save(slot) => object(vec![("type", string("save")), ("slot", number(*slot))]),
+    }
+}
+
+fn label_from_json(json: &Json) -> Result<Label, JsonError> {
+    let label = match as_str(field(json, "type")?, "type")? {
+        "epsilon" => Label::Epsilon,
+        "char" => Label::Char(as_char(field(json, "char")?, "char")?),
+        "class" => Label::Class(normalize_ranges(ranges_from_json(
+            field(json, "ranges")?,
+            "ranges",
+        )?)),
+        "save" => Label::Save(as_usize(field(json, "slot")?, "slot")?),
+        _ => return Err(invalid("type", "the name of a kind of label")),
+    };
+    Ok(label)
+}
+
+/// Serializes a NFA as an object listing its transitions, sorted by source state and in order
+/// for each source state:
+///
+/// ```text
+/// {
+///   "initial": 0,
+///   "accepting": 2,
+///   "transitions": [{"from": 0, "to": 1, "label": <label>}, ...]
+/// }
+/// ```
+///
+/// where a label is one of
+///
+/// ```text
+/// {"type": "epsilon"}
+/// {"type": "char", "char": "a"}
+/// {"type": "class", "ranges": [["a", "z"], ["_", "_"]]}
+/// {"type": "save", "slot": 0}
+/// ```
+///
+/// The states are written as they are, so a NFA should be renamed by `rename_states` first for
+/// its JSON to only depend on its structure.
+pub fn nfa_to_json(m: &Nfa) -> Json {
+    let mut states: Vec<&State> = m.transitions.keys().collect();
+    states.sort();
+    let transitions = states
+        .into_iter()
+        .flat_map(|q| {
+            m.transitions[q].iter().map(move |(label, next)| {
+                object(vec![
+                    ("from", number(*q)),
+                    ("to", number(*next)),
+                    ("label", label_to_json(label)),
+                ])
+            })
+        })
+        .collect();
+    object(vec![
+        ("initial", number(m.initial_state)),
+        ("accepting", number(m.accepting_state)),
+        ("transitions", Json::Array(transitions)),
+    ])
+}
+
+/// Reads back a NFA in the schema described on `nfa_to_json`, checking that no transition goes
+/// into its initial state or out of its accepting state.
+pub fn nfa_from_json(json: &Json) -> Result<Nfa, JsonError> {
+    let initial_state = as_usize(field(json, "initial")?, "initial")?;
+    let accepting_state = as_usize(field(json, "accepting")?, "accepting")?;
+    let mut transitions: HashMap<State, Vec<(Label, State)>> = HashMap::new();
+    for transition in as_array(field(json, "transitions")?, "transitions")? {
+        let from = as_usize(field(transition, "from")?, "from")?;
+        let to = as_usize(field(transition, "to")?, "to")?;
+        if to == initial_state {
+            return Err(invalid("to", "a state other than the initial state"));
+        }
+        if from == accepting_state {
+            return Err(invalid("from", "a state other than the accepting state"));
+        }
+        let label = label_from_json(field(transition, "label")?)?;
+        transitions.entry(from).or_default().push((label, to));
+    }
+    Ok(Nfa {
+        initial_state,
+        accepting_state,
+        transitions,
+    })
+}
+
+fn span_to_json(span: &Range<usize>, input_string: &str) -> Json {
+    object(vec![
+        ("start", number(span.start)),
+        ("end", number(span.end)),
+        ("text", string(&input_string[span.clone()])),
+    ])
+}
+
+/// Serializes a match found in `input_string` with the spans of its capture groups:
+///
+/// ```text
+/// {"start": 4, "end": 9, "text": "ab123", "groups": [<span> | null, ...]}
+/// ```
+///
+/// where "groups" has an element for every capture group from group 1 on, a span being
+/// `{"start": 6, "end": 9, "text": "123"}`, or null if the group took no part in the match.
+/// Offsets are byte offsets into the input string, and "text" is the matched part of it.
+pub fn captures_to_json(captures: &Captures, input_string: &str) -> Json {
+    let span = |span: &Option<Range<usize>>| {
+        span.as_ref()
+            .map_or(Json::Null, |span| span_to_json(span, input_string))
+    };
+    let Json::Object(mut members) = span(&captures[0]) else {
+        unreachable!("the whole match always has a span")
+    };
+    let groups = captures[1..].iter().map(span).collect();
+    members.push(("groups".to_string(), Json::Array(groups)));
+    Json::Object(members)
+}
+
+/// Reads back a match in the schema described on `captures_to_json`. The "text" members are not
+/// needed.
+pub fn captures_from_json(json: &Json) -> Result<Captures, JsonError> {
+    let span = |json: &Json| -> Result<Range<usize>, JsonError> {
+        let start = as_usize(field(json, "start")?, "start")?;
+        let end = as_usize(field(json, "end")?, "end")?;
+        if end < start {
+            return Err(invalid("end", "at least start"));
+        }
+        Ok(start..end)
+    };
+    let mut captures = vec![Some(span(json)?)];
+    for group in as_array(field(json, "groups")?, "groups")? {
+        captures.push(match group {
+            Json::Null => None,
+            group => Some(span(group)?),
+        });
+    }
+    Ok(captures)
+}
+
+fn invalid(field: &str, expected: &'static str) -> JsonError {
+    JsonError::InvalidValue {
+        field: field.to_string(),
+        expected,
+    }
+}
+
+/// Returns the member of an object with the given name.
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, JsonError> {
+    let Json::Object(members) = json else {
+        return Err(invalid("", "an object"));
+    };
+    members
+        .iter()
+        .find(|(member, _)| member == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| JsonError::MissingField {
+            field: name.to_string(),
+        })
+}
+
+fn as_str<'a>(json: &'a Json, name: &str) -> Result<&'a str, JsonError> {
+    match json {
+        Json::String(s) => Ok(s),
+        _ => Err(invalid(name, "a string")),
+    }
+}
+
+fn as_bool(json: &Json, name: &str) -> Result<bool, JsonError> {
+    match json {
+        Json::Bool(b) => Ok(*b),
+        _ => Err(invalid(name, "true or false")),
+    }
+}
+
+fn as_array<'a>(json: &'a Json, name: &str) -> Result<&'a [Json], JsonError> {
+    match json {
+        Json::Array(items) => Ok(items),
+        _ => Err(invalid(name, "an array")),
+    }
+}
+
+fn as_char(json: &Json, name: &str) -> Result<char, JsonError> {
+    let mut chars = as_str(json, name)?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(invalid(name, "a string of one character")),
+    }
+}
+
+fn as_usize(json: &Json, name: &str) -> Result<usize, JsonError> {
+    match json {
+        // from 2^53 on, f64 cannot tell every integer apart from its neighbours
+        Json::Number(n) if n.fract() == 0.0 && (0.0..2f64.powi(53)).contains(n) => Ok(*n as usize),
+        _ => Err(invalid(name, "a non-negative integer")),
+    }
+}
+
+fn as_u32(json: &Json, name: &str) -> Result<u32, JsonError> {
+    u32::try_from(as_usize(json, name)?).map_err(|_| invalid(name, "a non-negative integer"))
+}
+
+fn ranges_from_json(json: &Json, name: &str) -> Result<Vec<(char, char)>, JsonError> {
+    let mut ranges = Vec::new();
+    for range in as_array(json, name)? {
+        let (start, end) = match as_array(range, name)? {
+            [start, end] => (as_char(start, name)?, as_char(end, name)?),
+            _ => return Err(invalid(name, "an array of [start, end] pairs")),
+        };
+        if end < start {
+            return Err(invalid(name, "ranges whose start is not after their end"));
+        }
+        ranges.push((start, end));
+    }
+    Ok(ranges)
+}
@@ -0,0 +1,290 @@
+#![cfg(test)]
+
+use super::{
+    captures_from_json, captures_to_json, expr_from_json, expr_to_json, nfa_from_json, nfa_to_json,
+    parse_json, Json, JsonError, MAX_JSON_DEPTH,
+};
+use crate::{
+    stage_1::parse_regex,
+    stage_2::{convert_regex_to_nfa, rename_states, MAX_REPETITION},
+    stage_3::captures_iter,
+};
+
+#[test]
+fn test_parse_json() {
+    let value =
+        parse_json(" {\"a\": [1, -2.5e1, true, null], \"b\": \"x\\n\\u00e9\\ud83d\\ude00\"} ")
+            .unwrap();
+    assert_eq!(
+        value,
+        Json::Object(vec![
+            (
+                "a".to_string(),
+                Json::Array(vec![
+                    Json::Number(1.0),
+                    Json::Number(-25.0),
+                    Json::Bool(true),
+                    Json::Null
+                ])
+            ),
+            ("b".to_string(), Json::String("x\né😀".to_string())),
+        ])
+    );
+    assert_eq!(parse_json("[]").unwrap(), Json::Array(vec![]));
+    assert_eq!(parse_json("{}").unwrap(), Json::Object(vec![]));
+
+    for (input, offset) in [
+        ("", 0),
+        ("[1,]", 3),
+        ("[1 2]", 3),
+        ("{\"a\" 1}", 5),
+        ("01", 1),
+        ("1.", 2),
+        ("\"\\x\"", 1),
+        ("\"\\ud83d\"", 1),
+        ("\"\\u+041\"", 1),
+        ("\"a", 2),
+        ("nul", 0),
+        ("[1] [2]", 4),
+    ] {
+        assert_eq!(
+            parse_json(input),
+            Err(JsonError::Syntax { offset }),
+            "{:?}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_json_display_round_trip() {
+    let value = Json::Object(vec![
+        (
+            "quote\"".to_string(),
+            Json::String("\\ \t\u{1}ü".to_string()),
+        ),
+        (
+            "numbers".to_string(),
+            Json::Array(vec![
+                Json::Number(42.0),
+                Json::Number(-0.5),
+                Json::Number(1e20),
+            ]),
+        ),
+        ("empty".to_string(), Json::Object(vec![])),
+    ]);
+    let text = value.to_string();
+    assert_eq!(
+        text,
+        "{\"quote\\\"\":\"\\\\ \\t\\u0001ü\",\"numbers\":[42,-0.5,100000000000000000000],\"empty\":{}}"
+    );
+    assert_eq!(parse_json(&text).unwrap(), value);
+    assert_eq!(Json::Number(f64::NAN).to_string(), "null");
+}
+
+#[test]
+fn test_expr_json_round_trip() {
+    for pattern in [
+        "a",
+        "(a|b)*abb",
+        "[^a-z_]+x?",
+        "(?:ab){2,5}c{3,}",
+        ".\\.\\\\\"",
+        "\\w+&~(.*admin.*)",
+        "((a)|(b))",
+    ] {
+        let expr = parse_regex(pattern).unwrap();
+        let text = expr_to_json(&expr).to_string();
+        let read = expr_from_json(&parse_json(&text).unwrap()).unwrap();
+        assert_eq!(read, expr, "{}", pattern);
+    }
+
+    assert_eq!(
+        expr_to_json(&parse_regex("a{1,}").unwrap()).to_string(),
+        "{\"type\":\"repeat\",\"expr\":{\"type\":\"char\",\"char\":\"a\"},\"min\":1,\"max\":null}"
+    );
+}
+
+#[test]
+fn test_expr_from_json_errors() {
+    let error = |text: &str| expr_from_json(&parse_json(text).unwrap()).unwrap_err();
+    assert_eq!(
+        error("{\"char\":\"a\"}"),
+        JsonError::MissingField {
+            field: "type".to_string()
+        }
+    );
+    assert_eq!(
+        error("{\"type\":\"char\",\"char\":\"ab\"}").to_string(),
+        "field \"char\" should be a string of one character"
+    );
+    assert_eq!(
+        error("{\"type\":\"repeat\",\"expr\":{\"type\":\"any\"},\"min\":3,\"max\":2}").to_string(),
+        "field \"max\" should be at least min"
+    );
+    assert_eq!(
+        error("{\"type\":\"repeat\",\"expr\":{\"type\":\"any\"},\"min\":1.5,\"max\":null}")
+            .to_string(),
+        "field \"min\" should be a non-negative integer"
+    );
+    assert_eq!(
+        error("{\"type\":\"class\",\"ranges\":[[\"z\",\"a\"]],\"negated\":false}").to_string(),
+        "field \"ranges\" should be ranges whose start is not after their end"
+    );
+    assert_eq!(
+        error("{\"type\":\"star\",\"expr\":[]}").to_string(),
+        "expected an object"
+    );
+    assert_eq!(
+        error("{\"type\":\"lookahead\"}").to_string(),
+        "field \"type\" should be the name of a kind of expression"
+    );
+
+    // the expressions the parser would reject as too large are rejected too
+    assert_eq!(
+        error("{\"type\":\"repeat\",\"expr\":{\"type\":\"any\"},\"min\":4000000000,\"max\":null}")
+            .to_string(),
+        format!("field \"min\" should be at most {}", MAX_REPETITION)
+    );
+    assert_eq!(
+        error("{\"type\":\"repeat\",\"expr\":{\"type\":\"any\"},\"min\":0,\"max\":1001}")
+            .to_string(),
+        format!("field \"max\" should be at most {}", MAX_REPETITION)
+    );
+    let repeat = |expr: &str| {
+        format!(
+            "{{\"type\":\"repeat\",\"expr\":{},\"min\":1000,\"max\":1000}}",
+            expr
+        )
+    };
+    let any = "{\"type\":\"any\"}";
+    assert_eq!(
+        error(&format!(
+            "{{\"type\":\"not\",\"expr\":{}}}",
+            repeat(&repeat(any))
+        ))
+        .to_string(),
+        "field \"expr\" should be small enough to be repeated that many times"
+    );
+    let large = repeat(&format!(
+        "{{\"type\":\"concat\",\"left\":{},\"right\":{}}}",
+        any, any
+    ));
+    let mut concat = large.clone();
+    for _ in 0..30 {
+        concat = format!(
+            "{{\"type\":\"concat\",\"left\":{},\"right\":{}}}",
+            large, concat
+        );
+    }
+    assert_eq!(
+        error(&concat).to_string(),
+        "expected an expression whose nfa is not too large"
+    );
+    assert_eq!(
+        error("{\"type\":\"group\",\"expr\":{\"type\":\"any\"},\"index\":0}").to_string(),
+        "field \"index\" should be positive, as group 0 is the whole match"
+    );
+}
+
+#[test]
+fn test_json_nesting_limit() {
+    // reading a thousand levels recursively needs more than the stack of a test thread in a debug
+    // build
+    std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(check_json_nesting_limit)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn check_json_nesting_limit() {
+    let arrays = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(parse_json(&arrays(MAX_JSON_DEPTH)).is_ok());
+    assert_eq!(
+        parse_json(&arrays(MAX_JSON_DEPTH + 1)),
+        Err(JsonError::TooDeeplyNested {
+            offset: MAX_JSON_DEPTH
+        })
+    );
+
+    // the deepest expression the parser accepts can be read back
+    let expr = parse_regex(&"[a-z]".repeat(1000)).unwrap();
+    let text = expr_to_json(&expr).to_string();
+    assert_eq!(expr_from_json(&parse_json(&text).unwrap()).unwrap(), expr);
+
+    let stars = (0..1000).fold("{\"type\":\"any\"}".to_string(), |inner, _| {
+        format!("{{\"type\":\"star\",\"expr\":{}}}", inner)
+    });
+    assert_eq!(
+        expr_from_json(&parse_json(&stars).unwrap())
+            .unwrap_err()
+            .to_string(),
+        "field \"expr\" should be an expression nested less deeply"
+    );
+}
+
+#[test]
+fn test_nfa_json_round_trip() {
+    for pattern in ["(a|b)*abb", "(x)(y+)?[^\\n]", "~(a*)&[ab]{2}"] {
+        let mut m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        rename_states(&mut m);
+        let text = nfa_to_json(&m).to_string();
+        let read = nfa_from_json(&parse_json(&text).unwrap()).unwrap();
+        assert_eq!(read.initial_state, m.initial_state);
+        assert_eq!(read.accepting_state, m.accepting_state);
+        assert_eq!(read.transitions, m.transitions, "{}", pattern);
+        // the transitions are written in a canonical order
+        assert_eq!(nfa_to_json(&read).to_string(), text);
+    }
+
+    let error = |text: &str| nfa_from_json(&parse_json(text).unwrap()).unwrap_err();
+    let transition = |from: usize, to: usize| {
+        format!(
+            "{{\"initial\":0,\"accepting\":2,\"transitions\":[{{\"from\":{},\"to\":{},\
+             \"label\":{{\"type\":\"epsilon\"}}}}]}}",
+            from, to
+        )
+    };
+    assert_eq!(
+        error(&transition(1, 0)).to_string(),
+        "field \"to\" should be a state other than the initial state"
+    );
+    assert_eq!(
+        error(&transition(2, 1)).to_string(),
+        "field \"from\" should be a state other than the accepting state"
+    );
+    assert_eq!(
+        error("{\"initial\":0,\"accepting\":1}"),
+        JsonError::MissingField {
+            field: "transitions".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_captures_json_round_trip() {
+    let m = convert_regex_to_nfa(&parse_regex("([a-z]+)(\\d)?").unwrap());
+    let input = "éab1 xy";
+    let all: Vec<_> = captures_iter(&m, input).collect();
+    assert_eq!(all.len(), 2);
+
+    // offsets are in bytes, and groups which took no part in the match are null
+    assert_eq!(
+        captures_to_json(&all[0], input).to_string(),
+        "{\"start\":2,\"end\":5,\"text\":\"ab1\",\"groups\":[\
+         {\"start\":2,\"end\":4,\"text\":\"ab\"},{\"start\":4,\"end\":5,\"text\":\"1\"}]}"
+    );
+    assert_eq!(
+        captures_to_json(&all[1], input).to_string(),
+        "{\"start\":6,\"end\":8,\"text\":\"xy\",\"groups\":[{\"start\":6,\"end\":8,\"text\":\"xy\"},null]}"
+    );
+    for captures in all {
+        let text = captures_to_json(&captures, input).to_string();
+        assert_eq!(
+            captures_from_json(&parse_json(&text).unwrap()).unwrap(),
+            captures
+        );
+    }
+}
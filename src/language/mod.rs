@@ -0,0 +1,254 @@
+mod tests;
+
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+use crate::{
+    char_class::ranges_contain,
+    dfa::{
+        alphabet_classes, determinize, determinize_over, dfa_to_nfa, minimize, run_dfa, Dfa,
+        DfaError, DEFAULT_STATE_LIMIT,
+    },
+    Label, Nfa, State,
+};
+
+/// The outcome of comparing the languages of two automata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Equivalence {
+    /// Both automata accept exactly the same strings.
+    Equal,
+    /// `witness` is accepted by exactly one of the automata: by the first one if
+    /// `accepted_by_first` is true, and by the second one otherwise.
+    Different {
+        witness: String,
+        accepted_by_first: bool,
+    },
+}
+
+/// Decides whether two NFAs accept exactly the same strings.
+///
+/// Both NFAs are determinized over a shared alphabet, and the dfa accepting the strings accepted
+/// by exactly one of them is searched breadth first, trying the classes in order. The witness
+/// returned for different languages is thus a shortest distinguishing string, and among those the
+/// first in `char` order when each class is represented by its first character.
+///
+/// Fails if any of the dfas involved would have more than `DEFAULT_STATE_LIMIT` states.
+pub fn equivalent(m1: &Nfa, m2: &Nfa) -> Result<Equivalence, DfaError> {
+    let (dfa1, dfa2) = determinize_pair(m1, m2)?;
+    let either = product(&dfa1, &dfa2, |accepted1, accepted2| accepted1 != accepted2)?;
+    Ok(match shortest_accepted(&either) {
+        None => Equivalence::Equal,
+        Some(witness) => Equivalence::Different {
+            accepted_by_first: run_dfa(&dfa1, &witness),
+            witness,
+        },
+    })
+}
+
+/// Decides whether every string accepted by `m1` is also accepted by `m2`.
+///
+/// Fails if any of the dfas involved would have more than `DEFAULT_STATE_LIMIT` states.
+pub fn is_subset(m1: &Nfa, m2: &Nfa) -> Result<bool, DfaError> {
+    let (dfa1, dfa2) = determinize_pair(m1, m2)?;
+    let only_first = product(&dfa1, &dfa2, |accepted1, accepted2| accepted1 && !accepted2)?;
+    Ok(shortest_accepted(&only_first).is_none())
+}
+
+/// Returns a NFA accepting the strings accepted by both `m1` and `m2`.
+///
+/// Like all the operations of this module returning an automaton, the result is the minimal dfa
+/// of the language converted back with `dfa_to_nfa`, so it can be run, written to a file or
+/// combined further like any NFA built by `convert_regex_to_nfa`. Fails if any of the dfas
+/// involved would have more than `DEFAULT_STATE_LIMIT` states.
+pub fn intersect(m1: &Nfa, m2: &Nfa) -> Result<Nfa, DfaError> {
+    let (dfa1, dfa2) = determinize_pair(m1, m2)?;
+    let both = product(&dfa1, &dfa2, |accepted1, accepted2| accepted1 && accepted2)?;
+    Ok(dfa_to_nfa(&minimize(&both)))
+}
+
+/// Returns a NFA accepting exactly the strings which `m` rejects.
+pub fn complement(m: &Nfa) -> Result<Nfa, DfaError> {
+    let mut dfa = determinize(m)?;
+    // the dfa is complete, so swapping accepting and rejecting states swaps the languages
+    for accepting in dfa.accepting.iter_mut() {
+        *accepting = !*accepting;
+    }
+    Ok(dfa_to_nfa(&minimize(&dfa)))
+}
+
+/// Returns a NFA accepting the strings accepted by `m1` but rejected by `m2`.
+pub fn difference(m1: &Nfa, m2: &Nfa) -> Result<Nfa, DfaError> {
+    let (dfa1, dfa2) = determinize_pair(m1, m2)?;
+    let only_first = product(&dfa1, &dfa2, |accepted1, accepted2| accepted1 && !accepted2)?;
+    Ok(dfa_to_nfa(&minimize(&only_first)))
+}
+
+/// Returns a shortest string accepted by a NFA, or None if it accepts nothing.
+///
+/// The states of the NFA are searched breadth first, where epsilon transitions cost nothing and
+/// every other transition costs one character. A transition on a class reads a representative
+/// character of it, preferring ASCII letters and digits, then other printable ASCII characters,
+/// so the string makes a readable example of what the NFA accepts.
+pub fn shortest_match_string(m: &Nfa) -> Option<String> {
+    // the distance of every state reached so far, and the transition it was reached by
+    let mut distances: HashMap<State, usize> = HashMap::from([(m.initial_state, 0)]);
+    let mut reached_from: HashMap<State, (State, Option<char>)> = HashMap::new();
+    let mut unvisited = VecDeque::from([m.initial_state]);
+
+    while let Some(q) = unvisited.pop_front() {
+        if q == m.accepting_state {
+            break;
+        }
+        let distance = distances[&q];
+        for (label, next) in m.transitions.get(&q).into_iter().flatten() {
+            let (read, next_distance) = match label {
+                Label::Epsilon | Label::Save(_) => (None, distance),
+                Label::Char(c) => (Some(*c), distance + 1),
+                Label::Class(ranges) => match example_char(ranges) {
+                    Some(c) => (Some(c), distance + 1),
+                    None => continue,
+                },
+            };
+            if distances
+                .get(next)
+                .is_some_and(|known_distance| *known_distance <= next_distance)
+            {
+                continue;
+            }
+            distances.insert(*next, next_distance);
+            reached_from.insert(*next, (q, read));
+            // epsilon transitions go to the front so states are visited in order of distance
+            if read.is_none() {
+                unvisited.push_front(*next);
+            } else {
+                unvisited.push_back(*next);
+            }
+        }
+    }
+
+    if !distances.contains_key(&m.accepting_state) {
+        return None;
+    }
+    let mut reversed: Vec<char> = Vec::new();
+    let mut current = m.accepting_state;
+    while let Some((previous, read)) = reached_from.get(&current) {
+        reversed.extend(read);
+        current = *previous;
+    }
+    Some(reversed.into_iter().rev().collect())
+}
+
+/// Returns true if a NFA accepts no string at all.
+pub fn is_empty(m: &Nfa) -> bool {
+    shortest_match_string(m).is_none()
+}
+
+/// Picks a character of the normalized `ranges` to stand for the whole class in an example.
+fn example_char(ranges: &[(char, char)]) -> Option<char> {
+    let ascii = || (0..128u8).map(char::from);
+    ascii()
+        .find(|c| c.is_ascii_alphanumeric() && ranges_contain(ranges, *c))
+        .or_else(|| {
+            ascii().find(|c| (c.is_ascii_graphic() || *c == ' ') && ranges_contain(ranges, *c))
+        })
+        .or_else(|| ranges.first().map(|(start, _)| *start))
+}
+
+/// Builds the product of two dfas over the same classes, which runs both of them side by side.
+/// A pair of states is accepting if `accept` holds for whether each of the two states accepts.
+///
+/// Only the pairs reachable from the pair of initial states become states, numbered in breadth
+/// first order. Fails if there are more than `DEFAULT_STATE_LIMIT` of them.
+fn product(dfa1: &Dfa, dfa2: &Dfa, accept: impl Fn(bool, bool) -> bool) -> Result<Dfa, DfaError> {
+    let num_classes = dfa1.classes.len();
+    let initial = (dfa1.initial_state, dfa2.initial_state);
+    let mut state_ids: HashMap<(State, State), State> = HashMap::from([(initial, 0)]);
+    let mut pairs = vec![initial];
+    let mut transitions = Vec::new();
+
+    let mut i = 0;
+    while i < pairs.len() {
+        let (q1, q2) = pairs[i];
+        for class in 0..num_classes {
+            let next = (
+                dfa1.transitions[q1 * num_classes + class],
+                dfa2.transitions[q2 * num_classes + class],
+            );
+            let id = match state_ids.entry(next) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    if pairs.len() >= DEFAULT_STATE_LIMIT {
+                        return Err(DfaError::TooManyStates {
+                            limit: DEFAULT_STATE_LIMIT,
+                        });
+                    }
+                    pairs.push(next);
+                    *entry.insert(pairs.len() - 1)
+                }
+            };
+            transitions.push(id);
+        }
+        i += 1;
+    }
+
+    Ok(Dfa {
+        initial_state: 0,
+        accepting: pairs
+            .iter()
+            .map(|(q1, q2)| accept(dfa1.accepting[*q1], dfa2.accepting[*q2]))
+            .collect(),
+        classes: dfa1.classes.clone(),
+        transitions,
+    })
+}
+
+/// Returns a shortest string accepted by a dfa, or None if it accepts nothing.
+///
+/// The states are searched breadth first, trying the classes in order and reading the first
+/// character of each class, so among the shortest strings the first in `char` order is returned.
+fn shortest_accepted(dfa: &Dfa) -> Option<String> {
+    let num_classes = dfa.classes.len();
+    let mut reached_from: ReachedFrom<State> = HashMap::from([(dfa.initial_state, None)]);
+    let mut unvisited = VecDeque::from([dfa.initial_state]);
+
+    while let Some(q) = unvisited.pop_front() {
+        if dfa.accepting[q] {
+            return Some(path_to(&reached_from, &dfa.classes, q));
+        }
+        for class in 0..num_classes {
+            let next = dfa.transitions[q * num_classes + class];
+            if let Entry::Vacant(entry) = reached_from.entry(next) {
+                entry.insert(Some((q, class)));
+                unvisited.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Every state visited by a breadth first search, with the state and class it was first reached
+/// from, or None for the state the search started from.
+type ReachedFrom<T> = HashMap<T, Option<(T, usize)>>;
+
+/// Determinizes two NFAs over the same character classes, so their dfas can be run side by side.
+fn determinize_pair(m1: &Nfa, m2: &Nfa) -> Result<(Dfa, Dfa), DfaError> {
+    let classes = alphabet_classes(&[m1, m2]);
+    let dfa1 = determinize_over(m1, classes.clone(), DEFAULT_STATE_LIMIT)?;
+    let dfa2 = determinize_over(m2, classes, DEFAULT_STATE_LIMIT)?;
+    Ok((dfa1, dfa2))
+}
+
+/// Spells out the path of the breadth first search leading to `target`, reading the first
+/// character of each class on the way.
+fn path_to<T: Copy + Eq + std::hash::Hash>(
+    reached_from: &ReachedFrom<T>,
+    classes: &[(char, char)],
+    target: T,
+) -> String {
+    let mut reversed = Vec::new();
+    let mut current = target;
+    while let Some((previous, class)) = reached_from[&current] {
+        reversed.push(classes[class].0);
+        current = previous;
+    }
+    reversed.into_iter().rev().collect()
+}
@@ -0,0 +1,186 @@
+#![cfg(test)]
+
+use super::{
+    complement, difference, equivalent, intersect, is_empty, is_subset, shortest_match_string,
+    Equivalence,
+};
+use crate::{
+    nfa_to_file::write_nfa_to_file, stage_1::parse_regex, stage_2::convert_regex_to_nfa,
+    stage_3::run_nfa, Nfa,
+};
+
+fn nfa(pattern: &str) -> Nfa {
+    convert_regex_to_nfa(&parse_regex(pattern).unwrap())
+}
+
+#[test]
+fn test_equivalent_patterns() {
+    let pairs = [
+        ("a|b|c", "[a-c]"),
+        ("(a|b)*", "(a*b*)*"),
+        ("a+", "aa*"),
+        ("(ab)*a", "a(ba)*"),
+        ("x{2,3}", "xx|xxx"),
+        ("(a|b)*abb", "(?:a*b)*a+bb"),
+        ("\\d+", "[0-9][0-9]*"),
+        ("z{0}", "a{0}|b{0}"),
+    ];
+    for (pattern1, pattern2) in pairs {
+        assert_eq!(
+            equivalent(&nfa(pattern1), &nfa(pattern2)),
+            Ok(Equivalence::Equal),
+            "{} and {}",
+            pattern1,
+            pattern2
+        );
+    }
+}
+
+#[test]
+fn test_different_patterns() {
+    let cases = [
+        ("a*", "a+", "", true),
+        ("a+", "a*", "", false),
+        ("(ab)*", "(ab)*a?", "a", false),
+        ("[a-c]x", "[a-d]x", "dx", false),
+        ("colou?r", "color", "colour", true),
+        ("x{2,4}", "x{2,5}", "xxxxx", false),
+    ];
+    for (pattern1, pattern2, witness, accepted_by_first) in cases {
+        assert_eq!(
+            equivalent(&nfa(pattern1), &nfa(pattern2)),
+            Ok(Equivalence::Different {
+                witness: witness.to_string(),
+                accepted_by_first
+            }),
+            "{} and {}",
+            pattern1,
+            pattern2
+        );
+    }
+}
+
+#[test]
+fn test_witness_is_shortest() {
+    let m1 = nfa("(a|b)*a(a|b)");
+    let m2 = nfa("(a|b)*a(a|b)|bbb");
+    match equivalent(&m1, &m2).unwrap() {
+        Equivalence::Different {
+            witness,
+            accepted_by_first,
+        } => {
+            assert_eq!(witness, "bbb");
+            assert!(!accepted_by_first);
+            assert!(!run_nfa(&m1, &witness));
+            assert!(run_nfa(&m2, &witness));
+        }
+        Equivalence::Equal => panic!("the languages differ"),
+    }
+}
+
+#[test]
+fn test_is_subset() {
+    assert_eq!(is_subset(&nfa("ab+"), &nfa("a[a-c]*")), Ok(true));
+    assert_eq!(is_subset(&nfa("a[a-c]*"), &nfa("ab+")), Ok(false));
+    assert_eq!(is_subset(&nfa("x|y"), &nfa("y|x")), Ok(true));
+    assert_eq!(is_subset(&nfa("z{0}"), &nfa("a")), Ok(false));
+    assert_eq!(is_subset(&nfa("[a-z]{2}"), &nfa("[a-m].|[n-z].")), Ok(true));
+}
+
+#[test]
+fn test_intersect() {
+    let both = intersect(&nfa("[a-m]+"), &nfa("[h-z]+")).unwrap();
+    assert_eq!(equivalent(&both, &nfa("[h-m]+")), Ok(Equivalence::Equal));
+
+    // strings of a and b with an even length, ending with abb
+    let both = intersect(&nfa("((a|b)(a|b))*"), &nfa("(a|b)*abb")).unwrap();
+    for (input, expected) in [
+        ("babb", true),
+        ("abb", false),
+        ("aaabbb", false),
+        ("", false),
+    ] {
+        assert_eq!(run_nfa(&both, input), expected, "{:?}", input);
+    }
+}
+
+#[test]
+fn test_complement() {
+    let m = nfa("(a|b)*abb");
+    let not_m = complement(&m).unwrap();
+    for input in ["", "abb", "babb", "abba", "x", "abbx"] {
+        assert_eq!(run_nfa(&not_m, input), !run_nfa(&m, input), "{:?}", input);
+    }
+    assert_eq!(
+        equivalent(&complement(&not_m).unwrap(), &m),
+        Ok(Equivalence::Equal)
+    );
+}
+
+#[test]
+fn test_difference() {
+    // the old allow-list accepted any lowercase name, the new one no longer accepts digits at the
+    // end; the difference is what it newly rejects
+    let old = nfa("[a-z][a-z0-9]*");
+    let new = nfa("[a-z]+");
+    assert_eq!(is_subset(&new, &old), Ok(true));
+
+    let rejected = difference(&old, &new).unwrap();
+    assert_eq!(
+        equivalent(&rejected, &nfa("[a-z][a-z0-9]*[0-9][a-z0-9]*")),
+        Ok(Equivalence::Equal)
+    );
+    assert!(run_nfa(&rejected, "abc1"));
+    assert!(!run_nfa(&rejected, "abc"));
+
+    // no state of an empty language is live, so the automaton has no transitions at all
+    let nothing = difference(&new, &old).unwrap();
+    assert!(nothing.transitions.is_empty());
+    assert!(!run_nfa(&nothing, "abc"));
+}
+
+#[test]
+fn test_results_can_be_written() {
+    let path = std::env::temp_dir().join("fungex_test_results_can_be_written.txt");
+    let path = path.to_str().unwrap();
+    for m in [
+        intersect(&nfa("a+"), &nfa("b+")).unwrap(),
+        complement(&nfa(".*")).unwrap(),
+        difference(&nfa("[0-9]+"), &nfa("0[0-9]*")).unwrap(),
+    ] {
+        write_nfa_to_file(&m, path);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_shortest_match_string() {
+    let cases = [
+        ("abc", Some("abc")),
+        ("a*", Some("")),
+        ("x+y|zz", Some("xy")),
+        ("(ab|c)+d", Some("cd")),
+        ("[^a]b{2,}", Some("0bb")),
+        (".\\s", Some("0 ")),
+        ("\\d{3}", Some("000")),
+        ("[a-c]&[c-e]", Some("c")),
+        ("a&b", None),
+        ("~(.|\\n)*", None),
+    ];
+    for (pattern, expected) in cases {
+        let m = nfa(pattern);
+        assert_eq!(
+            shortest_match_string(&m).as_deref(),
+            expected,
+            "{}",
+            pattern
+        );
+        assert_eq!(is_empty(&m), expected.is_none(), "{}", pattern);
+    }
+
+    // the example is always accepted
+    let m = nfa("[^\\w]{2}(ab|[-~])");
+    let example = shortest_match_string(&m).unwrap();
+    assert_eq!(example.chars().count(), 3);
+    assert!(run_nfa(&m, &example));
+}
@@ -0,0 +1,231 @@
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+
+use crate::{
+    dfa::alphabet_classes,
+    stage_3::{do_epsilon_closure, do_transition},
+    Nfa, State,
+};
+
+/// The number of bytes of cached states `LazyDfa::new` allows before clearing its cache.
+pub const DEFAULT_CACHE_MEMORY: usize = 1 << 20;
+
+/// After this many cache clears within a single run, a run which keeps building new states
+/// rather than reusing cached ones gives up on the cache and simulates the NFA instead.
+const MAX_CACHE_CLEARS: usize = 3;
+
+/// A run is considered to be thrashing if it read fewer than this many characters per cached
+/// state since the last cache clear.
+const MIN_CHARS_PER_STATE: usize = 10;
+
+/// A dfa which is built lazily from a NFA while reading input.
+///
+/// Each dfa state is a set of NFA states, as in the subset construction of `determinize`, but a
+/// state and its transitions are only computed the first time the input leads there. Computed
+/// states are cached up to a memory budget; when the cache is full it is cleared and rebuilt from
+/// the current state on. If the cache keeps filling up without being reused, the run falls back to
+/// simulating the NFA directly, like `run_nfa`.
+///
+/// Capture slots are ignored: `Label::Save` transitions are treated as epsilon transitions.
+pub struct LazyDfa {
+    nfa: Nfa,
+    /// The character classes of the NFA, as given by `alphabet_classes`.
+    classes: Vec<(char, char)>,
+    /// The sorted epsilon closure of the NFA's initial state.
+    initial_set: Vec<State>,
+    memory_limit: usize,
+    cache: StateCache,
+    cache_clears: usize,
+    fallbacks: usize,
+}
+
+/// The dfa states computed so far.
+#[derive(Default)]
+struct StateCache {
+    /// `sets[q]` is the sorted, epsilon closed set of NFA states of dfa state q.
+    sets: Vec<Vec<State>>,
+    accepting: Vec<bool>,
+    state_ids: HashMap<Vec<State>, State>,
+    /// `transitions[q * classes.len() + i]` is the state reached from state q upon reading a
+    /// character of class i, or None if it was not computed yet.
+    transitions: Vec<Option<State>>,
+    /// The approximate number of bytes used by the cached states.
+    memory_used: usize,
+}
+
+impl LazyDfa {
+    /// Creates a lazy dfa for `nfa` whose cache may use up to `DEFAULT_CACHE_MEMORY` bytes.
+    pub fn new(nfa: Nfa) -> LazyDfa {
+        LazyDfa::with_memory_limit(nfa, DEFAULT_CACHE_MEMORY)
+    }
+
+    /// Same as `new`, but with a custom memory budget for the cache. A cache is always allowed to
+    /// hold at least one state, however small the budget.
+    pub fn with_memory_limit(nfa: Nfa, memory_limit: usize) -> LazyDfa {
+        let classes = alphabet_classes(&[&nfa]);
+        let mut initial_set = HashSet::from([nfa.initial_state]);
+        do_epsilon_closure(&mut initial_set, &nfa);
+        LazyDfa {
+            nfa,
+            classes,
+            initial_set: sorted(initial_set),
+            memory_limit,
+            cache: StateCache::default(),
+            cache_clears: 0,
+            fallbacks: 0,
+        }
+    }
+
+    /// The number of dfa states currently cached.
+    pub fn num_cached_states(&self) -> usize {
+        self.cache.sets.len()
+    }
+
+    /// The number of times the cache was cleared because it was full.
+    pub fn cache_clears(&self) -> usize {
+        self.cache_clears
+    }
+
+    /// The number of runs which gave up on the cache and fell back to simulating the NFA.
+    pub fn fallbacks(&self) -> usize {
+        self.fallbacks
+    }
+
+    /// Returns true if the NFA accepts the input string, like `run_nfa`.
+    pub fn run(&mut self, input_string: &str) -> bool {
+        self.walk(input_string, false)
+    }
+
+    /// Returns true if the NFA accepts some prefix of the input string, like `search_dfa`.
+    ///
+    /// For a lazy dfa of `unanchored_nfa(m)` this is true exactly when m accepts some substring of
+    /// the input string, like `search_nfa`.
+    pub fn search(&mut self, input_string: &str) -> bool {
+        self.walk(input_string, true)
+    }
+
+    /// Reads the input string from the initial state. If `stop_on_accept` is true, returns true as
+    /// soon as an accepting state is reached; otherwise returns whether the final state accepts.
+    fn walk(&mut self, input_string: &str, stop_on_accept: bool) -> bool {
+        let mut clears_this_run = 0;
+        let mut chars_since_clear = 0;
+
+        let initial_set = self.initial_set.clone();
+        let mut state = match self.cache.state_ids.get(&initial_set) {
+            Some(id) => *id,
+            None => {
+                if !self.has_room_for(&initial_set) {
+                    self.clear_cache();
+                }
+                self.insert(initial_set)
+            }
+        };
+        if stop_on_accept && self.cache.accepting[state] {
+            return true;
+        }
+
+        for (i, c) in input_string.char_indices() {
+            let class = self.class_of(c);
+            let index = state * self.classes.len() + class;
+            state = match self.cache.transitions[index] {
+                Some(next) => next,
+                None => {
+                    let mut next_set: HashSet<State> =
+                        self.cache.sets[state].iter().copied().collect();
+                    do_transition(&mut next_set, &self.nfa, c);
+                    do_epsilon_closure(&mut next_set, &self.nfa);
+                    let next_set = sorted(next_set);
+
+                    if let Some(next) = self.cache.state_ids.get(&next_set) {
+                        self.cache.transitions[index] = Some(*next);
+                        *next
+                    } else if self.has_room_for(&next_set) {
+                        let next = self.insert(next_set);
+                        self.cache.transitions[index] = Some(next);
+                        next
+                    } else {
+                        let thrashing = clears_this_run >= MAX_CACHE_CLEARS
+                            && chars_since_clear < MIN_CHARS_PER_STATE * self.num_cached_states();
+                        if thrashing {
+                            self.fallbacks += 1;
+                            let rest = &input_string[i + c.len_utf8()..];
+                            return self.simulate_nfa(next_set, rest, stop_on_accept);
+                        }
+
+                        // the transition is not recorded, as its source state is gone
+                        self.clear_cache();
+                        clears_this_run += 1;
+                        chars_since_clear = 0;
+                        self.insert(next_set)
+                    }
+                }
+            };
+            chars_since_clear += 1;
+
+            if stop_on_accept && self.cache.accepting[state] {
+                return true;
+            }
+        }
+
+        self.cache.accepting[state]
+    }
+
+    /// Continues a run on the rest of the input by simulating the NFA from the given set of states.
+    fn simulate_nfa(&self, states: Vec<State>, rest: &str, stop_on_accept: bool) -> bool {
+        let mut curr_states: HashSet<State> = states.into_iter().collect();
+        if stop_on_accept && curr_states.contains(&self.nfa.accepting_state) {
+            return true;
+        }
+        for c in rest.chars() {
+            do_transition(&mut curr_states, &self.nfa, c);
+            do_epsilon_closure(&mut curr_states, &self.nfa);
+            if stop_on_accept && curr_states.contains(&self.nfa.accepting_state) {
+                return true;
+            }
+        }
+        curr_states.contains(&self.nfa.accepting_state)
+    }
+
+    fn class_of(&self, c: char) -> usize {
+        self.classes.partition_point(|(start, _)| *start <= c) - 1
+    }
+
+    /// The approximate number of bytes a cached state with the given set of NFA states uses: the
+    /// set is stored twice (in `sets` and as a key of `state_ids`), along with a row of transitions.
+    fn state_memory(&self, set: &[State]) -> usize {
+        2 * set.len() * size_of::<State>() + self.classes.len() * size_of::<Option<State>>()
+    }
+
+    fn has_room_for(&self, set: &[State]) -> bool {
+        self.cache.sets.is_empty()
+            || self.cache.memory_used + self.state_memory(set) <= self.memory_limit
+    }
+
+    /// Adds a state for a set which is not cached yet, and returns it.
+    fn insert(&mut self, set: Vec<State>) -> State {
+        let id = self.cache.sets.len();
+        self.cache.memory_used += self.state_memory(&set);
+        self.cache
+            .accepting
+            .push(set.binary_search(&self.nfa.accepting_state).is_ok());
+        self.cache
+            .transitions
+            .extend(std::iter::repeat_n(None, self.classes.len()));
+        self.cache.state_ids.insert(set.clone(), id);
+        self.cache.sets.push(set);
+        id
+    }
+
+    fn clear_cache(&mut self) {
+        self.cache = StateCache::default();
+        self.cache_clears += 1;
+    }
+}
+
+fn sorted(states: HashSet<State>) -> Vec<State> {
+    let mut set: Vec<State> = states.into_iter().collect();
+    set.sort_unstable();
+    set
+}
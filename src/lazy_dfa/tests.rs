@@ -0,0 +1,88 @@
+#![cfg(test)]
+
+use super::LazyDfa;
+use crate::{
+    stage_1::parse_regex,
+    stage_2::{convert_regex_to_nfa, unanchored_nfa},
+    stage_3::{run_nfa, search_nfa},
+};
+
+#[test]
+fn test_lazy_dfa_agrees_with_nfa() {
+    let patterns = [
+        "a",
+        "(a*)|(bc)(d*)",
+        "[a-z_][a-z0-9_]*",
+        "\\d{2,4}-?x+",
+        ".a.",
+        "[^aeiou]*",
+    ];
+    let inputs = [
+        "", "a", "bcdd", "abc", "snake_9", "9snake", "12-x", "12345x", "xay", "a\na", "rhythm",
+    ];
+    for pattern in patterns {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        let mut lazy = LazyDfa::new(m.clone());
+        let mut search_lazy = LazyDfa::new(unanchored_nfa(&m));
+        for input in inputs {
+            assert_eq!(
+                lazy.run(input),
+                run_nfa(&m, input),
+                "{} on {:?}",
+                pattern,
+                input
+            );
+            assert_eq!(
+                search_lazy.search(input),
+                search_nfa(&m, input),
+                "{} searched in {:?}",
+                pattern,
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_lazy_dfa_reuses_cached_states() {
+    let m = convert_regex_to_nfa(&parse_regex("(a|b)*abb").unwrap());
+    let mut lazy = LazyDfa::new(m);
+
+    assert!(lazy.run("babaabb"));
+    let num_states = lazy.num_cached_states();
+    assert!(num_states <= 5);
+
+    // the same states cover any other input over a and b
+    assert!(!lazy.run("abbaabab"));
+    assert!(lazy.run("aaabb"));
+    assert_eq!(lazy.num_cached_states(), num_states);
+    assert_eq!(lazy.cache_clears(), 0);
+}
+
+#[test]
+fn test_lazy_dfa_cache_limit() {
+    // the dfa of (a|b)*a(a|b){n} needs 2^(n+1) states, far more than fit in the cache
+    let m = convert_regex_to_nfa(&parse_regex("(a|b)*a(a|b){8}").unwrap());
+    let inputs = [
+        "abbbbbbbb",
+        "bbbbbbbbbb",
+        "abaabbbabababbbaaabababbbbaab",
+        "babbaaabbbabbabaabbbaaababbbabbaaababbbabaaabbbabaababbbabbabb",
+    ];
+
+    // a budget which holds a few states: the cache is cleared now and then, but the answers
+    // stay the same
+    let mut lazy = LazyDfa::with_memory_limit(m.clone(), 2000);
+    for input in inputs {
+        assert_eq!(lazy.run(input), run_nfa(&m, input), "{:?}", input);
+    }
+    assert!(lazy.cache_clears() > 0);
+
+    // a budget of a single state makes every step build a new state, so runs give up on the cache
+    let mut thrashing = LazyDfa::with_memory_limit(m.clone(), 0);
+    for input in inputs {
+        assert_eq!(thrashing.run(input), run_nfa(&m, input), "{:?}", input);
+    }
+    assert!(thrashing.fallbacks() > 0);
+    assert!(thrashing.num_cached_states() <= 1);
+}
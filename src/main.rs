@@ -1,30 +1,206 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
+mod char_class;
+mod dfa;
+mod generate;
+mod json;
+mod language;
+mod lazy_dfa;
 mod nfa_to_file;
+mod printer;
+mod simplify;
 mod stage_1;
 mod stage_2;
 mod stage_3;
+mod state_elimination;
+mod svg;
 
-use std::{collections::HashMap, process::exit};
+use std::{collections::HashMap, ops::Range, process::exit};
 
-use nfa_to_file::write_nfa_to_pdf;
+use char_class::{ranges_contain, CharClass};
+use nfa_to_file::{parse_nfa_file, write_nfa_to_pdf};
 
-use crate::{stage_2::convert_regex_to_nfa, stage_3::run_nfa};
+use crate::{
+    dfa::{determinize, dfa_to_nfa, minimize, run_dfa, search_dfa, Dfa},
+    generate::shortlex_strings,
+    json::{captures_to_json, expr_to_json, nfa_to_json, Json},
+    language::{equivalent, is_empty, Equivalence},
+    lazy_dfa::LazyDfa,
+    simplify::{has_groups, simplify},
+    stage_1::parse_regex,
+    stage_2::{rename_states, try_convert_regex_to_nfa, unanchored_nfa},
+    stage_3::{captures, captures_iter, find_iter, Captures},
+    state_elimination::dfa_to_regex,
+};
+
+const USAGE: &str =
+    "usage: fungex [-x | --full-match] [-o | --only-matching] [--color] [--draw] [--minimize]
+              [--format text|json] (<regex> | --automaton FILE)
+
+Prints every line of stdin which contains a match of <regex>.
+  -x, --full-match       only print lines which match <regex> in their entirety
+  -o, --only-matching    print each non-empty match on its own line instead of the whole line
+      --color            highlight the matches within each printed line
+      --draw             also write the nfa of <regex> to output.txt and output.gv, draw it to
+                         output.svg, and draw it to output.pdf if Graphviz is installed
+      --minimize         draw the minimal dfa of <regex> instead of its nfa (implies --draw)
+      --automaton FILE   match with the nfa described in FILE, in the format of output.txt,
+                         instead of a regex expression
+      --format FORMAT    print plain lines (text, the default) or json: a first JSON line
+                         describing <regex> and its nfa, then one JSON line per matching line
+                         listing its matches and their capture groups (-o and --color are
+                         then ignored)
+
+usage: fungex equiv <regex1> <regex2>
+
+Checks whether <regex1> and <regex2> match exactly the same strings, and otherwise prints a
+shortest string matched by only one of them. Exits with 0 if they are equivalent, 1 if not.
+
+usage: fungex enumerate [--max-len N] [--limit K] <regex>
+
+Prints the strings matched by <regex> in their entirety, shortest first and then in character
+order, one quoted string per line.
+      --max-len N        only print strings of at most N characters (default 8)
+      --limit K          print at most K strings (default 20)
+
+usage: fungex simplify <regex>
+
+Prints a regex matching the same strings as <regex>, read off its minimal dfa. Capturing groups
+are not kept.";
+
+/// The options of a `fungex` invocation.
+struct CliOptions {
+    pattern: String,
+    /// If true, `pattern` is the path of a file describing a NFA, in the format written to
+    /// output.txt, rather than a regex expression.
+    automaton: bool,
+    /// If true, a line must be matched as a whole (like `grep -x`) rather than just contain a match.
+    full_match: bool,
+    only_matching: bool,
+    color: bool,
+    /// If true, the automaton is written and drawn to the output files before matching.
+    draw: bool,
+    /// If true, the automaton written and drawn to the output files is the minimal dfa rather than
+    /// the nfa.
+    minimize: bool,
+    /// If true, the automaton and the matches are printed as JSON lines, see `print_json_matches`.
+    json: bool,
+}
+
+fn parse_cli_options(args: &[String]) -> Result<CliOptions, String> {
+    let mut pattern = None;
+    let mut automaton = false;
+    let mut full_match = false;
+    let mut only_matching = false;
+    let mut color = false;
+    let mut draw = false;
+    let mut minimize = false;
+    let mut json = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-x" | "--full-match" => full_match = true,
+            "-o" | "--only-matching" => only_matching = true,
+            "--color" => color = true,
+            "--draw" => draw = true,
+            "--minimize" => minimize = true,
+            "--format" => match args.next().map(|format| format.as_str()) {
+                Some("text") => json = false,
+                Some("json") => json = true,
+                _ => return Err("--format takes either text or json".to_string()),
+            },
+            "--automaton" if pattern.is_none() => {
+                let file = args.next().ok_or("--automaton takes a file name")?;
+                pattern = Some(file.clone());
+                automaton = true;
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option '{}'", arg))
+            }
+            _ if pattern.is_none() => pattern = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    Ok(CliOptions {
+        pattern: pattern.ok_or("please give an argument which is a regex expression.")?,
+        automaton,
+        full_match,
+        only_matching,
+        color,
+        draw: draw || minimize,
+        minimize,
+        json,
+    })
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() <= 1 {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
         println!("please give an argument which is a regex expression.");
+        println!("{}", USAGE);
         exit(0);
     }
 
-    let arg_1 = args.get(1).unwrap();
+    match args[0].as_str() {
+        "equiv" => exit(equiv_command(&args[1..])),
+        "enumerate" => exit(enumerate_command(&args[1..])),
+        "simplify" => exit(simplify_command(&args[1..])),
+        _ => {}
+    }
+
+    let options = match parse_cli_options(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n{}", err, USAGE);
+            exit(2);
+        }
+    };
 
-    let expr = parse_regex(&arg_1);
+    let (expr, m) = if options.automaton {
+        (None, read_nfa_or_exit(&options.pattern))
+    } else {
+        let expr = parse_expr_or_exit(&options.pattern);
+        let m = convert_or_exit(&expr);
+        (Some(expr), m)
+    };
+    if is_empty(&m) {
+        eprintln!("warning: {} can never match anything", options.pattern);
+    }
+    // nothing is written unless asked for, so matching has no side effects
+    if options.draw && options.minimize {
+        match determinize(&m) {
+            Ok(dfa) => write_nfa_to_pdf(&dfa_to_nfa(&minimize(&dfa))),
+            Err(err) => {
+                eprintln!(
+                    "warning: drawing the nfa instead of the minimal dfa: {}",
+                    err
+                );
+                write_nfa_to_pdf(&m);
+            }
+        }
+    } else if options.draw {
+        write_nfa_to_pdf(&m);
+    }
 
-    let m = convert_regex_to_nfa(&expr);
-    write_nfa_to_pdf(&m);
+    // deciding whether a line matches is much faster on a dfa, so the whole dfa is built whenever
+    // it is small enough, and otherwise its states are built lazily while reading the lines
+    let line_nfa = if options.full_match {
+        m.clone()
+    } else {
+        unanchored_nfa(&m)
+    };
+    let mut line_matcher = match determinize(&line_nfa) {
+        Ok(dfa) => LineMatcher::Dfa(dfa),
+        Err(_) => LineMatcher::Lazy(LazyDfa::new(line_nfa)),
+    };
+
+    if options.json {
+        print_json_automaton(expr.as_ref(), &m, &options);
+    }
+    let mut line_number = 0;
 
     loop {
         let mut buffer = String::new();
@@ -32,151 +208,363 @@ fn main() {
         if buffer.is_empty() {
             exit(0);
         }
-        buffer = buffer.strip_suffix('\n').unwrap().to_string();
+        let line = buffer.strip_suffix('\n').unwrap_or(&buffer);
+        line_number += 1;
 
-        if run_nfa(&m, &buffer) {
-            println!("{}", buffer);
+        if options.json {
+            print_json_matches(&m, &mut line_matcher, line_number, line, &options);
+        } else {
+            print_matches(&m, &mut line_matcher, line, &options);
         }
     }
 }
 
-type State = usize;
-
-// First, go through our string, and for any adjacent chars (or parentheses), and insert a carat if necessary
-// If alphabet: add it to the end of a list
-// If open paren: push to stack
-// If close paren: pop everything until the last paren
-// If operator: push onto the stack
-#[derive(Debug, Clone)]
-struct Nfa {
-    initial_state: State,
-    accepting_state: State,
-    /// A transition from state q1 to q2 upon input character c will be represented as:
-    /// q1: [(c, q2), ...]   (the vec represents all outgoing transitions from q1)
-    /// If c is the zero byte '\0', then the transition is an epsilon transition
-    transitions: HashMap<State, Vec<(char, State)>>,
+/// Parses a pattern and converts it to a NFA, exiting with a diagnostic if either fails.
+fn parse_nfa_or_exit(pattern: &str) -> Nfa {
+    convert_or_exit(&parse_expr_or_exit(pattern))
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum RegexExpr {
-    SingleChar(char), // char should be a lowercase or uppercase letter (a-z or A-Z)
-    Star(Box<RegexExpr>),
-    Concat(Box<RegexExpr>, Box<RegexExpr>),
-    Or(Box<RegexExpr>, Box<RegexExpr>),
+/// Parses a pattern into the expression a NFA is built from, exiting with a diagnostic if that
+/// fails. The expression is simplified unless it has capturing groups, whose spans could change.
+fn parse_expr_or_exit(pattern: &str) -> RegexExpr {
+    match parse_regex(pattern) {
+        Ok(expr) if has_groups(&expr) => expr,
+        Ok(expr) => simplify(&expr),
+        Err(err) => {
+            eprintln!("{}", err.render(pattern));
+            exit(1);
+        }
+    }
 }
 
-fn print_vec(v: &Vec<char>) {
-    print!("[");
-    for item in v {
-        print!("{}, ", item);
+/// Converts an expression to a NFA, exiting with a diagnostic if that fails.
+fn convert_or_exit(expr: &RegexExpr) -> Nfa {
+    match try_convert_regex_to_nfa(expr) {
+        Ok(m) => m,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(1);
+        }
     }
-    println!("]");
 }
 
-/// Parses an input string representing a regex expression.
-/// The input string will consist of uppercase and lowercase English letters
-/// as well as characters '(' ')' for grouping expressions '*' for Kleene star and '|' for OR.
-///
-/// the input "ab|c" should be read as "(ab)|c",  with concatenation having higher precedence than OR.
-/// the input "a|b*" should be read as "a|(b*)",  with kleene star   having higher precedence than OR.
-/// the input "ab*" should be read as "a(b*)",    with kleene star   having higher precedence than concatenation.
-fn parse_regex(input_string: &str) -> RegexExpr {
-    let mut stack: Vec<char> = Vec::new();
-    let mut rps: String = String::new();
-
-    let chars: Vec<char> = input_string.chars().collect();
+/// Reads a NFA from a file in the format of `write_nfa_to_file`, exiting with a diagnostic if
+/// that fails.
+fn read_nfa_or_exit(filename: &str) -> Nfa {
+    let contents = match std::fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("error: cannot read {}: {}", filename, err);
+            exit(1);
+        }
+    };
+    match parse_nfa_file(&contents) {
+        Ok(m) => m,
+        Err(err) => {
+            eprintln!("error: {}: {}", filename, err);
+            exit(1);
+        }
+    }
+}
 
-    //println!("Original String: {}", input_string);
+/// Runs `fungex equiv <regex1> <regex2>` and returns its exit code.
+fn equiv_command(args: &[String]) -> i32 {
+    let [pattern1, pattern2] = args else {
+        eprintln!(
+            "error: equiv takes exactly two regex expressions\n{}",
+            USAGE
+        );
+        return 2;
+    };
+    let m1 = parse_nfa_or_exit(pattern1);
+    let m2 = parse_nfa_or_exit(pattern2);
 
-    for index in 0..chars.len() {
-        // print_vec(&stack);
+    match equivalent(&m1, &m2) {
+        Ok(Equivalence::Equal) => {
+            println!("equivalent");
+            0
+        }
+        Ok(Equivalence::Different {
+            witness,
+            accepted_by_first,
+        }) => {
+            let (matching, other) = if accepted_by_first {
+                (pattern1, pattern2)
+            } else {
+                (pattern2, pattern1)
+            };
+            println!(
+                "not equivalent: {:?} is matched by {} but not by {}",
+                witness, matching, other
+            );
+            1
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            2
+        }
+    }
+}
 
-        let c = chars[index];
+/// Runs `fungex enumerate [--max-len N] [--limit K] <regex>` and returns its exit code.
+fn enumerate_command(args: &[String]) -> i32 {
+    let mut pattern = None;
+    let mut max_len = 8;
+    let mut limit = 20;
 
-        if c.is_alphanumeric() || c == '*' {
-            rps.push(c);
-            if index + 1 < chars.len()
-                && (chars[index + 1].is_alphanumeric() || chars[index + 1] == '(')
-            {
-                stack.push('^');
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let count = match arg.as_str() {
+            "--max-len" => &mut max_len,
+            "--limit" => &mut limit,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                eprintln!("error: unknown option '{}'\n{}", arg, USAGE);
+                return 2;
             }
-        } else if c == '(' {
-            stack.push('(');
-        } else if c == ')' {
-            let mut curr = stack.pop();
-            while curr != None && curr != Some('(') {
-                rps.push(curr.unwrap());
-                curr = stack.pop();
+            _ if pattern.is_none() => {
+                pattern = Some(arg);
+                continue;
             }
-            if index + 1 < chars.len()
-                && (chars[index + 1].is_alphanumeric() || chars[index + 1] == '(')
-            {
-                stack.push('^');
+            _ => {
+                eprintln!("error: unexpected argument '{}'\n{}", arg, USAGE);
+                return 2;
             }
-        } else if c == '|' {
-            let mut curr = stack.pop();
-            while curr != None && curr == Some('^') {
-                rps.push(curr.unwrap());
-                curr = stack.pop();
+        };
+        match args.next().map(|value| value.parse::<usize>()) {
+            Some(Ok(value)) => *count = value,
+            _ => {
+                eprintln!("error: {} takes a non-negative number\n{}", arg, USAGE);
+                return 2;
             }
-            if curr != None {
-                stack.push(curr.unwrap());
+        }
+    }
+    let Some(pattern) = pattern else {
+        eprintln!("error: enumerate takes a regex expression\n{}", USAGE);
+        return 2;
+    };
+
+    let m = parse_nfa_or_exit(pattern);
+    match shortlex_strings(&m, max_len) {
+        Ok(strings) => {
+            for s in strings.take(limit) {
+                println!("{:?}", s);
             }
-            stack.push('|');
-        } else {
-            panic!("Regex character not recognized");
+            0
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            2
         }
     }
+}
+
+/// Runs `fungex simplify <regex>` and returns its exit code.
+fn simplify_command(args: &[String]) -> i32 {
+    let [pattern] = args else {
+        eprintln!(
+            "error: simplify takes exactly one regex expression\n{}",
+            USAGE
+        );
+        return 2;
+    };
+    let m = parse_nfa_or_exit(pattern);
 
-    while stack.len() > 0 {
-        let curr = stack.pop();
-        if (curr != None && curr != Some(')')) {
-            rps.push(curr.unwrap());
+    let dfa = match determinize(&m) {
+        Ok(dfa) => minimize(&dfa),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 2;
+        }
+    };
+    match dfa_to_regex(&dfa) {
+        Some(expr) => {
+            println!("{}", simplify(&expr));
+            0
+        }
+        None => {
+            eprintln!("{} can never match anything", pattern);
+            1
         }
     }
+}
 
-    //println!("Reverse Polish String: {}\n", rps);
+const HIGHLIGHT_START: &str = "\x1b[1;31m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
 
-    //Start from the end and move left, recursively right tree first
+/// Decides whether a line matches: runs the automaton of `m` when matching full lines, and of
+/// `unanchored_nfa(m)` otherwise.
+enum LineMatcher {
+    Dfa(Dfa),
+    Lazy(LazyDfa),
+}
 
-    let c_vec: Vec<char> = rps.chars().collect();
+impl LineMatcher {
+    fn run(&mut self, line: &str) -> bool {
+        match self {
+            LineMatcher::Dfa(dfa) => run_dfa(dfa, line),
+            LineMatcher::Lazy(lazy) => lazy.run(line),
+        }
+    }
 
-    if c_vec.len() <= 0 {
-        panic!("Empty Input String");
+    fn search(&mut self, line: &str) -> bool {
+        match self {
+            LineMatcher::Dfa(dfa) => search_dfa(dfa, line),
+            LineMatcher::Lazy(lazy) => lazy.search(line),
+        }
+    }
+}
+
+/// Prints the parts of `line` selected by the options, if `line` matches at all.
+fn print_matches(m: &Nfa, line_matcher: &mut LineMatcher, line: &str, options: &CliOptions) {
+    if options.full_match {
+        if line_matcher.run(line) {
+            println!("{}", highlight(line, options.color));
+        }
+        return;
+    }
+    if !options.only_matching && !options.color {
+        if line_matcher.search(line) {
+            println!("{}", line);
+        }
+        return;
+    }
+
+    let matches: Vec<Range<usize>> = find_iter(m, line).collect();
+    if matches.is_empty() {
+        return;
+    }
+    let non_empty_matches = matches.into_iter().filter(|found| !found.is_empty());
+
+    if options.only_matching {
+        for found in non_empty_matches {
+            println!("{}", highlight(&line[found], options.color));
+        }
+    } else {
+        let mut highlighted = String::new();
+        let mut pos = 0;
+        for found in non_empty_matches {
+            highlighted.push_str(&line[pos..found.start]);
+            highlighted.push_str(&highlight(&line[found.clone()], true));
+            pos = found.end;
+        }
+        highlighted.push_str(&line[pos..]);
+        println!("{}", highlighted);
     }
+}
 
-    tree_from_str(&c_vec, c_vec.len() - 1).0
+/// Prints the first JSON line of `--format json`: the pattern (or the name of the automaton
+/// file), the simplified expression the nfa was built from in the schema of `expr_to_json` (or
+/// null for an automaton file), and the nfa used for matching in the schema of `nfa_to_json`.
+fn print_json_automaton(expr: Option<&RegexExpr>, m: &Nfa, options: &CliOptions) {
+    let expr = expr.map_or(Json::Null, expr_to_json);
+    let mut m = m.clone();
+    rename_states(&mut m);
+    let automaton = Json::Object(vec![
+        ("pattern".to_string(), Json::String(options.pattern.clone())),
+        ("expr".to_string(), expr),
+        ("nfa".to_string(), nfa_to_json(&m)),
+    ]);
+    println!("{}", automaton);
 }
 
-fn tree_from_str(polish_str: &Vec<char>, start: usize) -> (RegexExpr, usize) {
-    let s_size: usize = polish_str.len();
+/// Prints a JSON line for a line of stdin containing a match, or matching as a whole with
+/// `--full-match`: `{"line": 3, "text": <the line>, "matches": [<match>, ...]}`, with its line
+/// number counting from 1 and its matches in the schema of `captures_to_json`.
+fn print_json_matches(
+    m: &Nfa,
+    line_matcher: &mut LineMatcher,
+    line_number: usize,
+    line: &str,
+    options: &CliOptions,
+) {
+    let matches: Vec<Captures> = if options.full_match {
+        if !line_matcher.run(line) {
+            return;
+        }
+        // the leftmost longest match of a line matching as a whole is the whole line
+        captures(m, line).into_iter().collect()
+    } else {
+        if !line_matcher.search(line) {
+            return;
+        }
+        captures_iter(m, line).collect()
+    };
+    let result = Json::Object(vec![
+        ("line".to_string(), Json::Number(line_number as f64)),
+        ("text".to_string(), Json::String(line.to_string())),
+        (
+            "matches".to_string(),
+            Json::Array(
+                matches
+                    .iter()
+                    .map(|found| captures_to_json(found, line))
+                    .collect(),
+            ),
+        ),
+    ]);
+    println!("{}", result);
+}
 
-    if start < 0 {
-        panic!("Invalid Regex String");
+fn highlight(text: &str, color: bool) -> String {
+    if color {
+        format!("{}{}{}", HIGHLIGHT_START, text, HIGHLIGHT_END)
+    } else {
+        text.to_string()
     }
+}
 
-    let size: usize = 0;
-    let curr = polish_str[start];
+type State = usize;
+
+#[derive(Debug, Clone)]
+struct Nfa {
+    initial_state: State,
+    accepting_state: State,
+    /// A transition from state q1 to q2 upon reading a character matched by label l will be represented as:
+    /// q1: [(l, q2), ...]   (the vec represents all outgoing transitions from q1)
+    transitions: HashMap<State, Vec<(Label, State)>>,
+}
 
-    if curr.is_alphanumeric() {
-        return (RegexExpr::SingleChar(curr), 1);
+/// The label of a single NFA transition.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Label {
+    /// An epsilon transition, which is taken without reading a character.
+    Epsilon,
+    /// Reads exactly the given character.
+    Char(char),
+    /// Reads any character inside one of the given normalized ranges (see `char_class`).
+    Class(Vec<(char, char)>),
+    /// An epsilon transition which also records the current input position in the given capture
+    /// slot. Capture group k is delimited by slots 2k (its start) and 2k+1 (its end).
+    Save(usize),
+}
+
+impl Label {
+    fn is_epsilon(&self) -> bool {
+        matches!(self, Label::Epsilon | Label::Save(_))
     }
-    if curr == '*' {
-        let (child, ct) = tree_from_str(polish_str, start - 1);
-        return (RegexExpr::Star(Box::new(child)), 1 + ct);
-    } else if curr == '^' {
-        let (childr, ctr) = tree_from_str(polish_str, start - 1);
-        let (childl, ctl) = tree_from_str(polish_str, start - ctr - 1);
-        return (
-            RegexExpr::Concat(Box::new(childl), Box::new(childr)),
-            1 + ctl + ctr,
-        );
-    } else {
-        let (childr, ctr) = tree_from_str(polish_str, start - 1);
-        let (childl, ctl) = tree_from_str(polish_str, start - ctr - 1);
-        return (
-            RegexExpr::Or(Box::new(childl), Box::new(childr)),
-            1 + ctl + ctr,
-        );
+
+    /// Returns true if this transition can be taken upon reading `c`.
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Label::Epsilon | Label::Save(_) => false,
+            Label::Char(label_char) => *label_char == c,
+            Label::Class(ranges) => ranges_contain(ranges, c),
+        }
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RegexExpr {
+    SingleChar(char),
+    Class(CharClass),
+    AnyChar, // matches any char except a newline
+    Star(Box<RegexExpr>),
+    Plus(Box<RegexExpr>),
+    Optional(Box<RegexExpr>),
+    Repeat(Box<RegexExpr>, u32, Option<u32>), // e{min,max}, where a max of None means unbounded
+    Concat(Box<RegexExpr>, Box<RegexExpr>),
+    Or(Box<RegexExpr>, Box<RegexExpr>),
+    Group(Box<RegexExpr>, usize), // a capturing group with its index, counting from 1
+    And(Box<RegexExpr>, Box<RegexExpr>), // matches the strings matched by both expressions
+    Not(Box<RegexExpr>),          // matches the strings not matched by the expression
+}
@@ -0,0 +1,353 @@
+mod tests;
+
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::iter::Peekable;
+use std::process::Command;
+use std::str::Chars;
+use std::{fs::File, path::Path};
+
+use crate::{stage_2::rename_states, svg::write_nfa_to_svg, Label, Nfa, State};
+
+/// Writes a NFA to a text file, which `parse_nfa_file` reads back: a line with the number of
+/// states n, the lines `initial q` and `accepting q` naming the initial and accepting states, and a
+/// `from to label` line for every transition, with the label formatted by `format_label`. The
+/// states are renamed 0 to n - 1 first by `rename_states`, and the transitions are written in
+/// the order of their source states, so the file only depends on the NFA.
+pub fn write_nfa_to_file(m: &Nfa, filename: &str) {
+    let mut m = m.clone();
+    rename_states(&mut m);
+
+    let num_states = m.accepting_state + 1;
+
+    let mut resulting_file_str = String::new();
+
+    resulting_file_str.push_str(&format!("{}\n", num_states));
+    resulting_file_str.push_str(&format!("initial {}\n", m.initial_state));
+    resulting_file_str.push_str(&format!("accepting {}\n", m.accepting_state));
+
+    // sorted by state so the same NFA is always written the same way
+    let mut states: Vec<&State> = m.transitions.keys().collect();
+    states.sort();
+    for start_state in states {
+        for (label, target_state) in m.transitions[start_state].iter() {
+            resulting_file_str.push_str(&format!(
+                "{} {} {}\n",
+                start_state,
+                target_state,
+                format_label(label)
+            ));
+        }
+    }
+
+    let path = Path::new(filename);
+
+    // Open a file in write-only mode, returns `io::Result<File>`
+    let mut file = File::create(path).unwrap();
+    file.write_all(resulting_file_str.as_bytes()).unwrap();
+}
+
+/// Formats a transition label as a single whitespace-free token:
+/// `\0` for an epsilon transition, the character itself for a single character, a bracket
+/// expression such as `[a-z_]` for a class, and `save:k` for a transition saving capture slot k.
+/// A class matching no character at all, as built for "[^\\s\\S]", is written `[]`.
+fn format_label(label: &Label) -> String {
+    match label {
+        Label::Epsilon => "\\0".to_string(),
+        Label::Save(slot) => format!("save:{}", slot),
+        Label::Char(c) => escape_label_char(*c, false),
+        Label::Class(ranges) => {
+            let mut s = String::from("[");
+            for (start, end) in ranges {
+                s.push_str(&escape_label_char(*start, true));
+                if start != end {
+                    s.push('-');
+                    s.push_str(&escape_label_char(*end, true));
+                }
+            }
+            s.push(']');
+            s
+        }
+    }
+}
+
+/// An error found while reading a NFA file written by `write_nfa_to_file`.
+/// Every variant carries the (1-based) number of the line at which the problem was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NfaFileError {
+    /// The first line is not a positive number of states.
+    InvalidStateCount { line: usize },
+    /// A line which is neither `initial q`, `accepting q` nor `from to label`.
+    InvalidLine { line: usize },
+    /// A second `initial` or `accepting` line.
+    DuplicateHeader { line: usize },
+    /// A state which is not below the number of states.
+    StateOutOfRange { state: State, line: usize },
+    /// A label which `format_label` could not have written.
+    InvalidLabel { line: usize },
+    /// A transition into the initial state, which must have no incoming transitions.
+    TransitionIntoInitial { line: usize },
+    /// A transition out of the accepting state, which must have no outgoing transitions.
+    TransitionFromAccepting { line: usize },
+    /// The initial state is also the accepting state. The line is the last one which gave either,
+    /// or the first line if the states are left to their defaults.
+    InitialIsAccepting { line: usize },
+}
+
+impl std::fmt::Display for NfaFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NfaFileError::InvalidStateCount { line } => {
+                write!(f, "line {}: expected a positive number of states", line)
+            }
+            NfaFileError::InvalidLine { line } => write!(
+                f,
+                "line {}: expected `initial q`, `accepting q` or `from to label`",
+                line
+            ),
+            NfaFileError::DuplicateHeader { line } => {
+                write!(f, "line {}: the state is already given", line)
+            }
+            NfaFileError::StateOutOfRange { state, line } => {
+                write!(f, "line {}: there is no state {}", line, state)
+            }
+            NfaFileError::InvalidLabel { line } => write!(f, "line {}: invalid label", line),
+            NfaFileError::TransitionIntoInitial { line } => {
+                write!(f, "line {}: transition into the initial state", line)
+            }
+            NfaFileError::TransitionFromAccepting { line } => {
+                write!(f, "line {}: transition out of the accepting state", line)
+            }
+            NfaFileError::InitialIsAccepting { line } => {
+                write!(
+                    f,
+                    "line {}: the initial state is also the accepting state",
+                    line
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for NfaFileError {}
+
+/// Reads a NFA from the contents of a file written by `write_nfa_to_file`, or written by hand in
+/// the same format. Empty lines are ignored, and the `initial` and `accepting` lines may be left
+/// out, in which case the initial state is 0 and the accepting state is the last one.
+///
+/// Like every NFA, the result must have distinct initial and accepting states, no transition into
+/// its initial state and none out of its accepting state, which is checked.
+pub fn parse_nfa_file(contents: &str) -> Result<Nfa, NfaFileError> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let (num_states, mut header_line) = match lines.next() {
+        Some((line, text)) => match text.parse::<usize>() {
+            Ok(n) if n > 0 => (n, line),
+            _ => return Err(NfaFileError::InvalidStateCount { line }),
+        },
+        None => return Err(NfaFileError::InvalidStateCount { line: 1 }),
+    };
+    let parse_state = |text: &str, line: usize| match text.parse::<State>() {
+        Ok(state) if state < num_states => Ok(state),
+        Ok(state) => Err(NfaFileError::StateOutOfRange { state, line }),
+        Err(_) => Err(NfaFileError::InvalidLine { line }),
+    };
+
+    let mut initial_state = None;
+    let mut accepting_state = None;
+    // every transition, with the line it was read from
+    let mut edges: Vec<(State, Label, State, usize)> = Vec::new();
+    for (line, text) in lines {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        match fields[..] {
+            [header, state] if header == "initial" || header == "accepting" => {
+                let slot = if header == "initial" {
+                    &mut initial_state
+                } else {
+                    &mut accepting_state
+                };
+                if slot.is_some() {
+                    return Err(NfaFileError::DuplicateHeader { line });
+                }
+                *slot = Some(parse_state(state, line)?);
+                header_line = line;
+            }
+            [from, to, label] => {
+                let label = parse_label(label).ok_or(NfaFileError::InvalidLabel { line })?;
+                edges.push((
+                    parse_state(from, line)?,
+                    label,
+                    parse_state(to, line)?,
+                    line,
+                ));
+            }
+            _ => return Err(NfaFileError::InvalidLine { line }),
+        }
+    }
+
+    let initial_state = initial_state.unwrap_or(0);
+    let accepting_state = accepting_state.unwrap_or(num_states - 1);
+    if initial_state == accepting_state {
+        return Err(NfaFileError::InitialIsAccepting { line: header_line });
+    }
+    let mut transitions: HashMap<State, Vec<(Label, State)>> = HashMap::new();
+    for (from, label, to, line) in edges {
+        if to == initial_state {
+            return Err(NfaFileError::TransitionIntoInitial { line });
+        }
+        if from == accepting_state {
+            return Err(NfaFileError::TransitionFromAccepting { line });
+        }
+        transitions.entry(from).or_default().push((label, to));
+    }
+    Ok(Nfa {
+        initial_state,
+        accepting_state,
+        transitions,
+    })
+}
+
+/// Reads back a label written by `format_label`.
+fn parse_label(token: &str) -> Option<Label> {
+    if token == "\\0" {
+        return Some(Label::Epsilon);
+    }
+    if let Some(slot) = token.strip_prefix("save:") {
+        return slot.parse().ok().map(Label::Save);
+    }
+    if let Some(inner) = token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let mut chars = inner.chars().peekable();
+        let mut ranges = Vec::new();
+        while chars.peek().is_some() {
+            let start = parse_label_char(&mut chars, true)?;
+            let end = if chars.next_if_eq(&'-').is_some() {
+                parse_label_char(&mut chars, true)?
+            } else {
+                start
+            };
+            if end < start {
+                return None;
+            }
+            ranges.push((start, end));
+        }
+        return Some(Label::Class(ranges));
+    }
+
+    let mut chars = token.chars().peekable();
+    let c = parse_label_char(&mut chars, false)?;
+    chars.peek().is_none().then_some(Label::Char(c))
+}
+
+/// Reads back a character escaped by `escape_label_char`.
+fn parse_label_char(chars: &mut Peekable<Chars>, in_class: bool) -> Option<char> {
+    match chars.next()? {
+        '\\' => match chars.next()? {
+            'u' => {
+                chars.next_if_eq(&'{')?;
+                let mut hex = String::new();
+                while let Some(c) = chars.next_if(|c| *c != '}') {
+                    hex.push(c);
+                }
+                chars.next_if_eq(&'}')?;
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)
+            }
+            c @ ('\\' | '[' | ']' | '-' | '^') => Some(c),
+            _ => None,
+        },
+        '[' | ']' => None,
+        '-' if in_class => None,
+        c => Some(c),
+    }
+}
+
+/// Formats a transition label for a drawing: like `format_label`, except that an epsilon
+/// transition is labelled "ε".
+pub fn display_label(label: &Label) -> String {
+    match label {
+        Label::Epsilon => "ε".to_string(),
+        label => format_label(label),
+    }
+}
+
+/// Escapes characters which would otherwise be ambiguous in a label token.
+fn escape_label_char(c: char, in_class: bool) -> String {
+    if c.is_whitespace() || c.is_control() {
+        format!("\\u{{{:x}}}", c as u32)
+    } else if c == '\\' || c == '[' || c == ']' || (in_class && (c == '-' || c == '^')) {
+        format!("\\{}", c)
+    } else {
+        c.to_string()
+    }
+}
+
+/// Writes a NFA in the DOT language of Graphviz, e.g. to be drawn with `dot -Tpdf`.
+///
+/// States are numbered like in `write_nfa_to_file`, transitions are labelled like there except
+/// that epsilon transitions are labelled "ε", the initial state is pointed to by an arrow coming
+/// from nowhere and the accepting state is drawn as a double circle.
+pub fn write_nfa_to_dot(m: &Nfa, mut out: impl Write) -> io::Result<()> {
+    let mut m = m.clone();
+    rename_states(&mut m);
+
+    writeln!(out, "digraph nfa {{")?;
+    writeln!(out, "    rankdir=LR;")?;
+    writeln!(out, "    node [shape=circle];")?;
+    writeln!(out, "    start [shape=point];")?;
+    for q in 0..=m.accepting_state {
+        if q == m.accepting_state {
+            writeln!(out, "    {} [shape=doublecircle];", q)?;
+        } else {
+            writeln!(out, "    {};", q)?;
+        }
+    }
+    writeln!(out, "    start -> {};", m.initial_state)?;
+
+    let mut states: Vec<&State> = m.transitions.keys().collect();
+    states.sort();
+    for q in states {
+        for (label, next) in m.transitions[q].iter() {
+            writeln!(
+                out,
+                "    {} -> {} [label=\"{}\"];",
+                q,
+                next,
+                escape_dot_string(&display_label(label))
+            )?;
+        }
+    }
+    writeln!(out, "}}")
+}
+
+/// Escapes the characters with a meaning inside a double quoted DOT string.
+fn escape_dot_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Writes a NFA to output.txt, in the DOT language to output.gv and as a drawing to output.svg,
+/// then also draws it to output.pdf if the `dot` program of Graphviz is installed.
+pub fn write_nfa_to_pdf(m: &Nfa) {
+    write_nfa_to_file(m, "output.txt");
+
+    let mut out = BufWriter::new(File::create("output.gv").unwrap());
+    write_nfa_to_dot(m, &mut out).unwrap();
+    out.flush().unwrap();
+
+    let mut out = BufWriter::new(File::create("output.svg").unwrap());
+    write_nfa_to_svg(m, &mut out).unwrap();
+    out.flush().unwrap();
+
+    match Command::new("dot")
+        .args(["-Tpdf", "output.gv", "-o", "output.pdf"])
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("warning: dot could not draw output.gv: {}", status),
+        // output.svg is drawing enough without Graphviz
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => eprintln!("warning: could not run dot to draw output.gv: {}", err),
+    }
+}
@@ -0,0 +1,172 @@
+#![cfg(test)]
+
+use super::{parse_nfa_file, write_nfa_to_dot, write_nfa_to_file, NfaFileError};
+use crate::{
+    language::{equivalent, Equivalence},
+    stage_1::parse_regex,
+    stage_2::{convert_regex_to_nfa, rename_states},
+    stage_3::run_nfa,
+    Label, Nfa,
+};
+
+fn dot(m: &Nfa) -> String {
+    let mut out = Vec::new();
+    write_nfa_to_dot(m, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_write_nfa_to_dot() {
+    let m = convert_regex_to_nfa(&parse_regex("a|b*").unwrap());
+    let dot = dot(&m);
+    let lines: Vec<&str> = dot.lines().collect();
+    assert_eq!(lines[0], "digraph nfa {");
+    assert_eq!(*lines.last().unwrap(), "}");
+
+    // states are numbered from 0 at the initial state to the accepting state
+    let num_states = m.transitions.len() + 1;
+    let accepting = format!("    {} [shape=doublecircle];", num_states - 1);
+    assert!(lines.contains(&accepting.as_str()), "{}", dot);
+    assert!(lines.contains(&"    start -> 0;"), "{}", dot);
+
+    let edges: Vec<&&str> = lines.iter().filter(|l| l.contains("label=")).collect();
+    let num_edges: usize = m.transitions.values().map(|t| t.len()).sum();
+    assert_eq!(edges.len(), num_edges);
+    assert!(edges.iter().any(|l| l.ends_with("[label=\"a\"];")));
+    assert!(edges.iter().any(|l| l.ends_with("[label=\"b\"];")));
+    assert!(edges.iter().any(|l| l.ends_with("[label=\"ε\"];")));
+}
+
+#[test]
+fn test_write_nfa_to_dot_escapes_labels() {
+    let m = convert_regex_to_nfa(&parse_regex("\"|\\\\|[\\]-a]|(x)").unwrap());
+    let dot = dot(&m);
+    assert!(dot.contains("[label=\"\\\"\"];"), "{}", dot);
+    assert!(dot.contains("[label=\"\\\\\\\\\"];"), "{}", dot);
+    assert!(dot.contains("[label=\"[\\\\]-a]\"];"), "{}", dot);
+    assert!(dot.contains("[label=\"save:2\"];"), "{}", dot);
+}
+
+/// Writes a NFA to a file and reads it back.
+fn round_trip(m: &Nfa, name: &str) -> Nfa {
+    let path = std::env::temp_dir().join(format!("fungex_test_{}.txt", name));
+    let path = path.to_str().unwrap();
+    write_nfa_to_file(m, path);
+    let contents = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    parse_nfa_file(&contents).unwrap()
+}
+
+#[test]
+fn test_parse_nfa_file_round_trip() {
+    for (i, pattern) in [
+        "(a|b)*abb",
+        "[a-z_][a-z0-9_]*",
+        "[\\]\\-^\\\\]|\\[|-|\\^",
+        "(x)(y+)?\\s\\t",
+        "[^a]",
+        "~(a*)&[ab]{2}",
+        "a[^\\s\\S]|b",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        let read = round_trip(&m, &format!("round_trip_{}", i));
+
+        let mut renamed = m.clone();
+        rename_states(&mut renamed);
+        assert_eq!(read.initial_state, renamed.initial_state, "{}", pattern);
+        assert_eq!(read.accepting_state, renamed.accepting_state, "{}", pattern);
+        assert_eq!(read.transitions, renamed.transitions, "{}", pattern);
+        assert_eq!(equivalent(&m, &read).unwrap(), Equivalence::Equal);
+    }
+}
+
+#[test]
+fn test_parse_nfa_file_by_hand() {
+    // the header lines may be left out, and the last state is then the accepting one
+    let m = parse_nfa_file("3\n0 1 a\n1 1 [0-9]\n\n1 2 \\0\n").unwrap();
+    assert_eq!((m.initial_state, m.accepting_state), (0, 2));
+    assert!(run_nfa(&m, "a"));
+    assert!(run_nfa(&m, "a123"));
+    assert!(!run_nfa(&m, "1"));
+
+    let m = parse_nfa_file("3\naccepting 0\ninitial 2\n2 1 save:0\n1 0 \\u{20}\n").unwrap();
+    assert_eq!((m.initial_state, m.accepting_state), (2, 0));
+    assert_eq!(m.transitions[&1], vec![(Label::Char(' '), 0)]);
+    assert_eq!(m.transitions[&2], vec![(Label::Save(0), 1)]);
+}
+
+#[test]
+fn test_parse_nfa_file_errors() {
+    let cases = [
+        ("", NfaFileError::InvalidStateCount { line: 1 }),
+        ("0\n", NfaFileError::InvalidStateCount { line: 1 }),
+        ("\nthree\n", NfaFileError::InvalidStateCount { line: 2 }),
+        ("2\n0 1\n", NfaFileError::InvalidLine { line: 2 }),
+        ("2\n0 one a\n", NfaFileError::InvalidLine { line: 2 }),
+        ("2\nfinal 1\n", NfaFileError::InvalidLine { line: 2 }),
+        (
+            "2\ninitial 0\ninitial 0\n",
+            NfaFileError::DuplicateHeader { line: 3 },
+        ),
+        (
+            "2\n0 2 a\n",
+            NfaFileError::StateOutOfRange { state: 2, line: 2 },
+        ),
+        (
+            "2\naccepting 5\n",
+            NfaFileError::StateOutOfRange { state: 5, line: 2 },
+        ),
+        ("2\n0 1 ab\n", NfaFileError::InvalidLabel { line: 2 }),
+        ("2\n0 1 [z-a]\n", NfaFileError::InvalidLabel { line: 2 }),
+        ("2\n0 1 \\q\n", NfaFileError::InvalidLabel { line: 2 }),
+        ("2\n0 1 save:x\n", NfaFileError::InvalidLabel { line: 2 }),
+        (
+            "3\n0 1 a\n1 0 a\n",
+            NfaFileError::TransitionIntoInitial { line: 3 },
+        ),
+        (
+            "3\n0 2 a\n2 1 a\n",
+            NfaFileError::TransitionFromAccepting { line: 3 },
+        ),
+        // a single state would be both the initial and the accepting state
+        ("1\n", NfaFileError::InitialIsAccepting { line: 1 }),
+        (
+            "3\naccepting 1\n0 2 a\ninitial 1\n",
+            NfaFileError::InitialIsAccepting { line: 4 },
+        ),
+    ];
+    for (contents, expected) in cases {
+        assert_eq!(
+            parse_nfa_file(contents).err(),
+            Some(expected),
+            "{:?}",
+            contents
+        );
+    }
+}
+
+#[test]
+fn test_write_nfa_to_file_is_reproducible() {
+    let path = std::env::temp_dir().join("fungex_test_write_nfa_to_file_is_reproducible.txt");
+    let path = path.to_str().unwrap();
+    let mut written = Vec::new();
+    // every NFA built has its own randomly ordered HashMap of transitions
+    for _ in 0..5 {
+        let m = convert_regex_to_nfa(&parse_regex("(a|b)*a(c|d)|[x-z]+").unwrap());
+        write_nfa_to_file(&m, path);
+        written.push(std::fs::read_to_string(path).unwrap());
+    }
+    std::fs::remove_file(path).unwrap();
+    assert!(written.iter().all(|w| *w == written[0]));
+
+    // the transitions are written in the order of their source states
+    let sources: Vec<usize> = written[0]
+        .lines()
+        .skip(3)
+        .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+        .collect();
+    assert!(sources.windows(2).all(|pair| pair[0] <= pair[1]));
+}
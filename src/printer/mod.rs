@@ -0,0 +1,154 @@
+mod tests;
+
+use std::fmt::{self, Write};
+
+use crate::{char_class::negate_ranges, CharClass, RegexExpr};
+
+/// Prints an expression in the syntax read by `parse_regex`, using as few parentheses as the
+/// precedence rules described there allow.
+///
+/// Parentheses added only for grouping are written as "(?:...)" so they do not capture, and
+/// characters with a meaning in the syntax are escaped. Since the binary operators are right
+/// associative, a left nested `Concat`, `Or` or `And` is parenthesized, e.g.
+/// `Concat(Concat(a, b), c)` prints as "(?:ab)c".
+///
+/// Every expression returned by `parse_regex` prints as a pattern which parses back into an equal
+/// expression, i.e. `parse_regex(&e.to_string()) == Ok(e)`.
+impl fmt::Display for RegexExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_expr(f, self, 0)
+    }
+}
+
+/// How tightly each kind of expression binds, from `Or` (loosest) to atoms (tightest).
+fn precedence(expr: &RegexExpr) -> u8 {
+    match expr {
+        RegexExpr::Or(_, _) => 1,
+        RegexExpr::And(_, _) => 2,
+        RegexExpr::Concat(_, _) => 3,
+        RegexExpr::Not(_) => 4,
+        RegexExpr::Star(_)
+        | RegexExpr::Plus(_)
+        | RegexExpr::Optional(_)
+        | RegexExpr::Repeat(..) => 5,
+        RegexExpr::SingleChar(_)
+        | RegexExpr::Class(_)
+        | RegexExpr::AnyChar
+        | RegexExpr::Group(_, _) => 6,
+    }
+}
+
+/// Writes `expr`, parenthesized if it binds less tightly than `min_precedence`.
+fn write_expr(f: &mut fmt::Formatter<'_>, expr: &RegexExpr, min_precedence: u8) -> fmt::Result {
+    let parenthesized = precedence(expr) < min_precedence;
+    if parenthesized {
+        f.write_str("(?:")?;
+    }
+
+    match expr {
+        RegexExpr::SingleChar(c) => f.write_str(&escape_char(*c, false))?,
+        RegexExpr::Class(class) => write_class(f, class)?,
+        RegexExpr::AnyChar => f.write_char('.')?,
+        RegexExpr::Star(e) => {
+            write_expr(f, e, 5)?;
+            f.write_char('*')?;
+        }
+        RegexExpr::Plus(e) => {
+            write_expr(f, e, 5)?;
+            f.write_char('+')?;
+        }
+        RegexExpr::Optional(e) => {
+            write_expr(f, e, 5)?;
+            f.write_char('?')?;
+        }
+        RegexExpr::Repeat(e, min, max) => {
+            write_expr(f, e, 5)?;
+            match max {
+                Some(max) if max == min => write!(f, "{{{}}}", min)?,
+                Some(max) => write!(f, "{{{},{}}}", min, max)?,
+                None => write!(f, "{{{},}}", min)?,
+            }
+        }
+        // the left operand of a right associative operator binds one level more tightly
+        RegexExpr::Concat(e1, e2) => {
+            write_expr(f, e1, 4)?;
+            write_expr(f, e2, 3)?;
+        }
+        RegexExpr::Or(e1, e2) => {
+            write_expr(f, e1, 2)?;
+            f.write_char('|')?;
+            write_expr(f, e2, 1)?;
+        }
+        RegexExpr::And(e1, e2) => {
+            write_expr(f, e1, 3)?;
+            f.write_char('&')?;
+            write_expr(f, e2, 2)?;
+        }
+        RegexExpr::Not(e) => {
+            f.write_char('~')?;
+            write_expr(f, e, 4)?;
+        }
+        RegexExpr::Group(e, _) => {
+            f.write_char('(')?;
+            write_expr(f, e, 0)?;
+            f.write_char(')')?;
+        }
+    }
+
+    if parenthesized {
+        f.write_char(')')?;
+    }
+    Ok(())
+}
+
+fn write_class(f: &mut fmt::Formatter<'_>, class: &CharClass) -> fmt::Result {
+    // "[^]" and "[]" do not parse, so a class of nothing, negated or not, is written as the
+    // class of everything, negated or not the other way round
+    let (ranges, negated) = if class.ranges.is_empty() {
+        (negate_ranges(&class.ranges), !class.negated)
+    } else {
+        (class.ranges.clone(), class.negated)
+    };
+
+    f.write_char('[')?;
+    if negated {
+        f.write_char('^')?;
+    }
+    for (start, end) in ranges {
+        f.write_str(&escape_char(start, true))?;
+        if start != end {
+            f.write_char('-')?;
+            f.write_str(&escape_char(end, true))?;
+        }
+    }
+    f.write_char(']')
+}
+
+/// Escapes a character so it stands for itself, outside of or inside a bracket expression.
+fn escape_char(c: char, in_class: bool) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\0' => "\\0".to_string(),
+        c if c.is_control() || (c.is_whitespace() && c != ' ') || is_private_or_noncharacter(c) => {
+            format!("\\u{{{:x}}}", c as u32)
+        }
+        '\\' | ']' => format!("\\{}", c),
+        '-' | '^' if in_class => format!("\\{}", c),
+        '.' | '[' | '(' | ')' | '{' | '}' | '*' | '+' | '?' | '|' | '&' | '~' if !in_class => {
+            format!("\\{}", c)
+        }
+        c => c.to_string(),
+    }
+}
+
+/// Returns true for the code points which are never assigned a visible character: the private use
+/// areas and the noncharacters.
+fn is_private_or_noncharacter(c: char) -> bool {
+    let code = c as u32;
+    (0xE000..=0xF8FF).contains(&code)
+        || code >= 0xF0000
+        || (0xFDD0..=0xFDEF).contains(&code)
+        || code & 0xFFFE == 0xFFFE
+}
@@ -0,0 +1,186 @@
+#![cfg(test)]
+
+use crate::{
+    simplify::simplify,
+    stage_1::parse_regex,
+    stage_2::{state_count_bound, MAX_NFA_STATES},
+    CharClass, RegexExpr,
+};
+
+#[test]
+fn test_print_regex() {
+    for pattern in [
+        "a",
+        "ab|c",
+        "a(b|c)*",
+        "(?:ab)*c?",
+        "a+|b{2}|c{2,}|d{2,5}",
+        "[_a-z][^0-9]",
+        ".x",
+        "a&~b*c",
+        "~(?:ab)",
+        "(a|b)&c",
+        "(?:a|b)&c",
+        "x**",
+        "\\.\\*\\(\\)\\[\\]\\{\\}\\|\\&\\~\\\\",
+        "\\n\\t\\0\\u{1f}",
+        "[\\-\\]a]",
+        "[\\-\\\\-\\^]",
+    ] {
+        assert_eq!(parse_regex(pattern).unwrap().to_string(), pattern);
+    }
+}
+
+#[test]
+fn test_print_regex_minimal_parentheses() {
+    let a = || Box::new(RegexExpr::SingleChar('a'));
+    let b = || Box::new(RegexExpr::SingleChar('b'));
+    let c = || Box::new(RegexExpr::SingleChar('c'));
+
+    // a left nested binary operator needs parentheses, a right nested one does not
+    let expr = RegexExpr::Concat(Box::new(RegexExpr::Concat(a(), b())), c());
+    assert_eq!(expr.to_string(), "(?:ab)c");
+    let expr = RegexExpr::Or(a(), Box::new(RegexExpr::Or(b(), c())));
+    assert_eq!(expr.to_string(), "a|b|c");
+    let expr = RegexExpr::Concat(Box::new(RegexExpr::Or(a(), b())), c());
+    assert_eq!(expr.to_string(), "(?:a|b)c");
+
+    let expr = RegexExpr::Star(Box::new(RegexExpr::Not(a())));
+    assert_eq!(expr.to_string(), "(?:~a)*");
+    let expr = RegexExpr::Concat(Box::new(RegexExpr::Not(a())), b());
+    assert_eq!(expr.to_string(), "~ab");
+    let expr = RegexExpr::Not(Box::new(RegexExpr::Concat(a(), b())));
+    assert_eq!(expr.to_string(), "~(?:ab)");
+
+    let expr = RegexExpr::Class(CharClass::new(vec![('-', '-'), ('a', 'a')], true));
+    assert_eq!(expr.to_string(), "[^\\-a]");
+    let expr = RegexExpr::Class(CharClass::new(vec![('\0', char::MAX)], false));
+    assert_eq!(expr.to_string(), "[\\0-\\u{10ffff}]");
+}
+
+/// Characters which are special somewhere in the syntax, mixed with some which are not.
+const TRICKY_CHARS: &[char] = &[
+    'a',
+    'b',
+    'z',
+    '0',
+    '9',
+    '\\',
+    '.',
+    '[',
+    ']',
+    '(',
+    ')',
+    '{',
+    '}',
+    '*',
+    '+',
+    '?',
+    '|',
+    '&',
+    '~',
+    '-',
+    '^',
+    ',',
+    '$',
+    ' ',
+    '\n',
+    '\t',
+    '\0',
+    '\u{1f}',
+    '\u{a0}',
+    'é',
+    '\u{e000}',
+    '\u{10ffff}',
+];
+
+/// Builds random expressions from a xorshift generator, numbering capturing groups in the order
+/// of their opening parenthesis, like `parse_regex` numbers them.
+struct ExprGenerator {
+    state: u64,
+    num_groups: usize,
+}
+
+impl ExprGenerator {
+    fn below(&mut self, bound: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % bound as u64) as usize
+    }
+
+    fn char(&mut self) -> char {
+        TRICKY_CHARS[self.below(TRICKY_CHARS.len())]
+    }
+
+    fn expr(&mut self, depth: usize) -> RegexExpr {
+        let num_kinds = if depth == 0 { 3 } else { 12 };
+        match self.below(num_kinds) {
+            0 => RegexExpr::SingleChar(self.char()),
+            1 => {
+                let ranges = (0..1 + self.below(3))
+                    .map(|_| {
+                        let (c1, c2) = (self.char(), self.char());
+                        (c1.min(c2), c1.max(c2))
+                    })
+                    .collect();
+                RegexExpr::Class(CharClass::new(ranges, self.below(2) == 0))
+            }
+            2 => RegexExpr::AnyChar,
+            3 => RegexExpr::Star(self.child(depth)),
+            4 => RegexExpr::Plus(self.child(depth)),
+            5 => RegexExpr::Optional(self.child(depth)),
+            6 => {
+                let min = self.below(3) as u32;
+                let max = match self.below(3) {
+                    0 => None,
+                    extra => Some(min + extra as u32 - 1),
+                };
+                RegexExpr::Repeat(self.child(depth), min, max)
+            }
+            7 => RegexExpr::Concat(self.child(depth), self.child(depth)),
+            8 => RegexExpr::Or(self.child(depth), self.child(depth)),
+            9 => RegexExpr::And(self.child(depth), self.child(depth)),
+            10 => RegexExpr::Not(self.child(depth)),
+            _ => {
+                self.num_groups += 1;
+                let index = self.num_groups;
+                RegexExpr::Group(self.child(depth), index)
+            }
+        }
+    }
+
+    fn child(&mut self, depth: usize) -> Box<RegexExpr> {
+        Box::new(self.expr(depth - 1))
+    }
+}
+
+#[test]
+fn test_display_round_trip() {
+    let mut generator = ExprGenerator {
+        state: 0x2545_f491_4f6c_dd1d,
+        num_groups: 0,
+    };
+    let mut checked = 0;
+    while checked < 3000 {
+        generator.num_groups = 0;
+        let depth = 1 + generator.below(5);
+        let expr = generator.expr(depth);
+        // the parser rejects expressions whose nfa would be too large
+        if state_count_bound(&expr) > MAX_NFA_STATES {
+            continue;
+        }
+        let printed = expr.to_string();
+        assert_eq!(parse_regex(&printed), Ok(expr), "{}", printed);
+        checked += 1;
+    }
+
+    // a class matching nothing, as simplifying "[^\\s\\S]" gives, prints as a pattern too
+    for pattern in ["[^\\s\\S]", "a[^\\s\\S]|b"] {
+        let expr = simplify(&parse_regex(pattern).unwrap());
+        let printed = expr.to_string();
+        assert_eq!(parse_regex(&printed), Ok(expr), "{}", printed);
+    }
+    let nothing = RegexExpr::Class(CharClass::new(vec![], false));
+    assert!(parse_regex(&nothing.to_string()).is_ok());
+}
@@ -0,0 +1,284 @@
+mod tests;
+
+use crate::{
+    stage_1::join_run,
+    state_elimination::{char_ranges, class_expr, nullable},
+    RegexExpr,
+};
+
+/// Rewrites an expression into a smaller one matching the same strings, by applying algebraic
+/// identities bottom up until none applies any more:
+/// - nested repetitions collapse, e.g. "(a*)*", "(a+)?" and "(a?|b*)*" become "a*" and "[a-b]*",
+///   and trivial bounded repetitions become the matching operator, e.g. "a{0,}" becomes "a*",
+/// - alternations are flattened, duplicate alternatives are dropped, alternatives matching single
+///   characters are merged into one class, alternatives with a common first factor are factored
+///   ("ab|ac" becomes "a[b-c]"), and the alternatives are sorted,
+/// - an alternative matching only the empty string, such as "a{0}", turns the rest optional,
+/// - "aa*" and "a*a" become "a+", "a*a*" becomes "a*", "~~a" becomes "a" and "a&a" becomes "a".
+///
+/// The alternatives of an alternation containing a capturing group are not merged, factored or
+/// reordered, since the order of alternatives decides which groups take part in a match, and a
+/// capturing group is kept as written, since rewriting its contents changes the text it captures:
+/// "(a|ab)(b?)" would become "(ab?)(b?)", which captures "ab" and "" in "ab" rather than "a" and "b".
+/// The rewrites around a group may still move text from one group to another, e.g. "(?:a|ab)(b?)",
+/// so matches whose groups matter should be made with the expression as written, see `has_groups`.
+pub fn simplify(expr: &RegexExpr) -> RegexExpr {
+    let mut current = expr.clone();
+    loop {
+        let next = simplify_once(&current);
+        if next == current {
+            return next;
+        }
+        current = next;
+    }
+}
+
+fn simplify_once(expr: &RegexExpr) -> RegexExpr {
+    match expr {
+        RegexExpr::SingleChar(_) | RegexExpr::AnyChar => expr.clone(),
+        RegexExpr::Class(class) => class_expr(class.matched_ranges()),
+        RegexExpr::Star(e) => star(simplify_once(e)),
+        RegexExpr::Plus(e) => plus(simplify_once(e)),
+        RegexExpr::Optional(e) => optional(simplify_once(e)),
+        RegexExpr::Repeat(e, min, max) => repeat(simplify_once(e), *min, *max),
+        RegexExpr::Concat(e1, e2) => {
+            let mut factors = factors_of(simplify_once(e1));
+            factors.extend(factors_of(simplify_once(e2)));
+            concat_all(factors)
+        }
+        RegexExpr::Or(e1, e2) => {
+            let mut alternatives = alternatives_of(simplify_once(e1));
+            alternatives.extend(alternatives_of(simplify_once(e2)));
+            alternation(alternatives)
+        }
+        RegexExpr::And(e1, e2) => {
+            let (e1, e2) = (simplify_once(e1), simplify_once(e2));
+            if e1 == e2 {
+                e1
+            } else {
+                RegexExpr::And(Box::new(e1), Box::new(e2))
+            }
+        }
+        RegexExpr::Not(e) => match simplify_once(e) {
+            RegexExpr::Not(inner) => *inner,
+            e => RegexExpr::Not(Box::new(e)),
+        },
+        RegexExpr::Group(_, _) => expr.clone(),
+    }
+}
+
+/// Returns true for an expression which matches only the empty string, like "a{0}".
+fn is_epsilon(expr: &RegexExpr) -> bool {
+    matches!(expr, RegexExpr::Repeat(_, 0, Some(0)))
+}
+
+/// Returns true for an expression containing a capturing group.
+pub fn has_groups(expr: &RegexExpr) -> bool {
+    match expr {
+        RegexExpr::SingleChar(_) | RegexExpr::Class(_) | RegexExpr::AnyChar => false,
+        RegexExpr::Group(_, _) => true,
+        RegexExpr::Star(e)
+        | RegexExpr::Plus(e)
+        | RegexExpr::Optional(e)
+        | RegexExpr::Repeat(e, _, _)
+        | RegexExpr::Not(e) => has_groups(e),
+        RegexExpr::Concat(e1, e2) | RegexExpr::Or(e1, e2) | RegexExpr::And(e1, e2) => {
+            has_groups(e1) || has_groups(e2)
+        }
+    }
+}
+
+fn star(expr: RegexExpr) -> RegexExpr {
+    match expr {
+        RegexExpr::Star(e) | RegexExpr::Plus(e) | RegexExpr::Optional(e) => star(*e),
+        e if is_epsilon(&e) => e,
+        // repeating any number of times already allows repeating each alternative any number of
+        // times, so their own repetitions are redundant, e.g. (a*|b)* is (a|b)*
+        RegexExpr::Or(e1, e2) if !has_groups(&e1) && !has_groups(&e2) => {
+            let alternatives = alternatives_of(RegexExpr::Or(e1, e2))
+                .into_iter()
+                .map(|alternative| match alternative {
+                    RegexExpr::Star(e) | RegexExpr::Plus(e) | RegexExpr::Optional(e) => *e,
+                    e => e,
+                })
+                .filter(|alternative| !is_epsilon(alternative))
+                .collect();
+            RegexExpr::Star(Box::new(alternation(alternatives)))
+        }
+        e => RegexExpr::Star(Box::new(e)),
+    }
+}
+
+fn plus(expr: RegexExpr) -> RegexExpr {
+    match expr {
+        RegexExpr::Plus(e) => RegexExpr::Plus(e),
+        // e+ is e* as soon as e matches the empty string
+        e if nullable(&e) => star(e),
+        e => RegexExpr::Plus(Box::new(e)),
+    }
+}
+
+fn optional(expr: RegexExpr) -> RegexExpr {
+    match expr {
+        RegexExpr::Plus(e) => star(*e),
+        e if nullable(&e) => e,
+        e => RegexExpr::Optional(Box::new(e)),
+    }
+}
+
+fn repeat(expr: RegexExpr, min: u32, max: Option<u32>) -> RegexExpr {
+    match (min, max) {
+        (1, Some(1)) => expr,
+        (0, None) => star(expr),
+        (1, None) => plus(expr),
+        (0, Some(1)) => optional(expr),
+        _ => RegexExpr::Repeat(Box::new(expr), min, max),
+    }
+}
+
+/// Flattens a (right or left nested) concatenation into the list of its factors.
+fn factors_of(expr: RegexExpr) -> Vec<RegexExpr> {
+    match expr {
+        RegexExpr::Concat(e1, e2) => {
+            let mut factors = factors_of(*e1);
+            factors.extend(factors_of(*e2));
+            factors
+        }
+        e => vec![e],
+    }
+}
+
+/// Flattens a (right or left nested) alternation into the list of its alternatives.
+fn alternatives_of(expr: RegexExpr) -> Vec<RegexExpr> {
+    match expr {
+        RegexExpr::Or(e1, e2) => {
+            let mut alternatives = alternatives_of(*e1);
+            alternatives.extend(alternatives_of(*e2));
+            alternatives
+        }
+        e => vec![e],
+    }
+}
+
+/// Concatenates a non-empty list of factors, joined like `parse_regex` joins them.
+fn concat_all(factors: Vec<RegexExpr>) -> RegexExpr {
+    let num_factors = factors.len();
+    let mut merged: Vec<RegexExpr> = Vec::new();
+    for factor in factors {
+        // the empty string is the unit of concatenation
+        if is_epsilon(&factor) && num_factors > 1 {
+            continue;
+        }
+        let previous = merged.pop();
+        match (previous, factor) {
+            (Some(RegexExpr::Star(a)), RegexExpr::Star(b)) if a == b => {
+                merged.push(RegexExpr::Star(a))
+            }
+            (Some(RegexExpr::Star(a)), b) | (Some(b), RegexExpr::Star(a)) if *a == b => {
+                merged.push(RegexExpr::Plus(a))
+            }
+            (previous, factor) => {
+                merged.extend(previous);
+                merged.push(factor);
+            }
+        }
+    }
+
+    if merged.is_empty() {
+        // every factor was the empty string
+        return RegexExpr::Repeat(Box::new(RegexExpr::AnyChar), 0, Some(0));
+    }
+    join_run(merged, &|factor, rest| {
+        RegexExpr::Concat(Box::new(factor), Box::new(rest))
+    })
+}
+
+/// Joins a non-empty list of alternatives, like `parse_regex` joins them.
+fn or_all(alternatives: Vec<RegexExpr>) -> RegexExpr {
+    join_run(alternatives, &|alternative, rest| {
+        RegexExpr::Or(Box::new(alternative), Box::new(rest))
+    })
+}
+
+/// Builds the simplest alternation of a non-empty list of already simplified alternatives.
+fn alternation(alternatives: Vec<RegexExpr>) -> RegexExpr {
+    let mut unique: Vec<RegexExpr> = Vec::new();
+    for alternative in alternatives {
+        if !unique.contains(&alternative) {
+            unique.push(alternative);
+        }
+    }
+    if unique.iter().any(has_groups) {
+        return or_all(unique);
+    }
+
+    let (epsilons, mut rest): (Vec<RegexExpr>, Vec<RegexExpr>) =
+        unique.into_iter().partition(is_epsilon);
+    if rest.is_empty() {
+        return epsilons.into_iter().next().unwrap();
+    }
+
+    // merge the alternatives matching a single character into one class, in place of the first
+    let single_chars: Vec<usize> = (0..rest.len())
+        .filter(|i| char_ranges(&rest[*i]).is_some())
+        .collect();
+    if single_chars.len() > 1 {
+        let ranges = single_chars
+            .iter()
+            .flat_map(|i| char_ranges(&rest[*i]).unwrap())
+            .collect();
+        rest[single_chars[0]] = class_expr(ranges);
+        for i in single_chars[1..].iter().rev() {
+            rest.remove(*i);
+        }
+    }
+
+    let mut alternatives = factor_prefixes(rest);
+    alternatives.sort_by_cached_key(|alternative| alternative.to_string());
+    let result = or_all(alternatives);
+    if epsilons.is_empty() {
+        result
+    } else {
+        optional(result)
+    }
+}
+
+/// Replaces every set of alternatives starting with the same factor by that factor followed by
+/// the alternation of their remainders, e.g. "ab|c|ad" becomes "a(?:b|d)|c".
+fn factor_prefixes(alternatives: Vec<RegexExpr>) -> Vec<RegexExpr> {
+    // the alternatives split into their factors, grouped by their first factor
+    let mut groups: Vec<(RegexExpr, Vec<Vec<RegexExpr>>)> = Vec::new();
+    for alternative in alternatives {
+        let mut factors = factors_of(alternative);
+        let first = factors.remove(0);
+        match groups.iter_mut().find(|(prefix, _)| *prefix == first) {
+            Some((_, remainders)) => remainders.push(factors),
+            None => groups.push((first, vec![factors])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(prefix, remainders)| {
+            if remainders.len() == 1 {
+                let mut factors = vec![prefix];
+                factors.extend(remainders.into_iter().next().unwrap());
+                return concat_all(factors);
+            }
+            let epsilon = RegexExpr::Repeat(Box::new(RegexExpr::AnyChar), 0, Some(0));
+            let remainders = remainders
+                .into_iter()
+                .map(|factors| {
+                    if factors.is_empty() {
+                        epsilon.clone()
+                    } else {
+                        concat_all(factors)
+                    }
+                })
+                .collect();
+            let mut factors = vec![prefix];
+            factors.extend(factors_of(alternation(remainders)));
+            concat_all(factors)
+        })
+        .collect()
+}
@@ -0,0 +1,119 @@
+#![cfg(test)]
+
+use super::simplify;
+use crate::{
+    language::{equivalent, Equivalence},
+    stage_1::parse_regex,
+    stage_2::convert_regex_to_nfa,
+    stage_3::captures,
+    RegexExpr,
+};
+
+fn simplified(pattern: &str) -> String {
+    simplify(&parse_regex(pattern).unwrap()).to_string()
+}
+
+#[test]
+fn test_simplify_nested_repetitions() {
+    let a = || Box::new(RegexExpr::SingleChar('a'));
+    assert_eq!(
+        simplify(&RegexExpr::Star(Box::new(RegexExpr::Star(a())))),
+        RegexExpr::Star(a())
+    );
+
+    assert_eq!(simplified("(?:a*)*"), "a*");
+    assert_eq!(simplified("(?:a+)?"), "a*");
+    assert_eq!(simplified("(?:a?)*"), "a*");
+    assert_eq!(simplified("(?:a+)+"), "a+");
+    assert_eq!(simplified("(?:a*)+"), "a*");
+    assert_eq!(simplified("(?:a{0}|a)*"), "a*");
+    assert_eq!(simplified("(?:a*|b)*"), "[a-b]*");
+    assert_eq!(simplified("(?:a?|b*)*"), "[a-b]*");
+    assert_eq!(simplified("a{0,}b{1,}c{0,1}d{1}"), "a*b+c?d");
+    assert_eq!(simplified("a{2,5}"), "a{2,5}");
+}
+
+#[test]
+fn test_simplify_alternations() {
+    assert_eq!(simplified("a|a"), "a");
+    assert_eq!(simplified("b|a"), "[a-b]");
+    assert_eq!(simplified("(?:cd|ab)|cd"), "ab|cd");
+    assert_eq!(simplified("a|b|c"), "[a-c]");
+    assert_eq!(simplified("x|[0-9]|yz|\\d"), "[0-9x]|yz");
+    assert_eq!(simplified("a{0}|ab"), "(?:ab)?");
+    assert_eq!(simplified("ab|ac"), "a[b-c]");
+    assert_eq!(simplified("abc|abd|e"), "ab[c-d]|e");
+    assert_eq!(simplified("ab|a"), "ab?");
+    assert_eq!(simplified("aa*"), "a+");
+    assert_eq!(simplified("a*a"), "a+");
+    assert_eq!(simplified("a*a*"), "a*");
+    assert_eq!(simplified("~~a"), "a");
+    assert_eq!(simplified("a&a"), "a");
+}
+
+#[test]
+fn test_simplify_keeps_groups_in_order() {
+    assert_eq!(simplified("(b)|(a)"), "(b)|(a)");
+    assert_eq!(simplified("(b)|a|(b)c"), "(b)|a|(b)c");
+    assert_eq!(simplified("(a|a)"), "(a|a)");
+    assert_eq!(simplified("(?:(?:a*)*|b)(b|a|b)"), "(?:a*|b)(b|a|b)");
+}
+
+#[test]
+fn test_simplify_keeps_captures() {
+    for (pattern, input) in [
+        ("(a|ab)(b?)", "ab"),
+        ("((?:a*)*)(a?)", "aa"),
+        ("x(b|a|ab)+(b*)|y", "xabab"),
+        ("(ab|ac|a)(c?)", "ac"),
+    ] {
+        let expr = parse_regex(pattern).unwrap();
+        let (m1, m2) = (
+            convert_regex_to_nfa(&expr),
+            convert_regex_to_nfa(&simplify(&expr)),
+        );
+        assert_eq!(captures(&m1, input), captures(&m2, input), "{}", pattern);
+    }
+    assert_eq!(
+        captures(
+            &convert_regex_to_nfa(&simplify(&parse_regex("(a|ab)(b?)").unwrap())),
+            "ab"
+        ),
+        Some(vec![Some(0..2), Some(0..1), Some(1..2)])
+    );
+}
+
+#[test]
+fn test_simplify_preserves_language() {
+    for pattern in [
+        "(?:a*)*b|c|c",
+        "(?:a|b)*abb|(?:a|b)*abb",
+        "ab|ac|a{0}|ad*",
+        "[a-c]|d|\\d|x*x",
+        "(?:x?|y+)*z{0,1}",
+        "(a|b)(c|d)|(a|b)e",
+        "~~(?:ab|ac)&.*c",
+        "a{0}|a{0}",
+        "(?:abc|abd|ab)+",
+        "a*a*b+b*",
+    ] {
+        let expr = parse_regex(pattern).unwrap();
+        let simple = simplify(&expr);
+        let (m1, m2) = (convert_regex_to_nfa(&expr), convert_regex_to_nfa(&simple));
+        assert_eq!(
+            equivalent(&m1, &m2).unwrap(),
+            Equivalence::Equal,
+            "{} became {}",
+            pattern,
+            simple
+        );
+        assert!(
+            m2.transitions.len() <= m1.transitions.len(),
+            "{} became {}",
+            pattern,
+            simple
+        );
+        // simplifying is idempotent
+        assert_eq!(simplify(&simple), simple);
+    }
+}
@@ -1 +1,685 @@
 pub mod tests;
+
+use std::ops::Range;
+
+use crate::{
+    stage_2::{state_count_bound, MAX_NFA_STATES, MAX_REPETITION},
+    CharClass, RegexExpr,
+};
+
+/// The deepest an expression may be nested, so that the parser and everything which recurses over
+/// the parsed expression stay well within the stack.
+pub const MAX_NESTING_DEPTH: usize = 1000;
+
+/// The most operands of a binary operator which are joined from the right into a single chain, see
+/// `join_run`.
+pub const MAX_RUN_LENGTH: usize = 32;
+
+/// An error produced while parsing a regex expression.
+/// Every variant carries the byte offset into the input string at which the problem was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A stray ']' or '}', which only have a meaning as the end of a bracket expression or a repetition.
+    UnknownChar { ch: char, offset: usize },
+    /// A `(` that is never closed, or a `)` that closes nothing.
+    UnbalancedParen { offset: usize },
+    /// An operator that is missing an operand, e.g. the `*` in "*a" or the `|` in "a|".
+    DanglingOperator { op: char, offset: usize },
+    /// The whole input, or a parenthesized group, contains no expression at all.
+    EmptyPattern { offset: usize },
+    /// A `[` whose bracket expression is never closed by a `]`.
+    UnterminatedClass { offset: usize },
+    /// A bracket expression with no characters in it, i.e. "[]" or "[^]".
+    EmptyClass { offset: usize },
+    /// A range inside a bracket expression whose end comes before its start, e.g. "z-a".
+    InvalidRange { offset: usize },
+    /// A `{` that does not start a well-formed repetition `{m}`, `{m,}` or `{m,n}` with m <= n.
+    InvalidRepetition { offset: usize },
+    /// A repetition whose count exceeds `MAX_REPETITION`, or which would make the nfa built for
+    /// the expression larger than `MAX_NFA_STATES`.
+    RepetitionTooLarge { offset: usize },
+    /// A '\\' that does not start one of the escape sequences described on `parse_regex`.
+    InvalidEscape { offset: usize },
+    /// An expression which, as a whole, would make its nfa larger than `MAX_NFA_STATES`.
+    PatternTooLarge { offset: usize },
+    /// An expression nested more than `MAX_NESTING_DEPTH` levels deep.
+    TooDeeplyNested { offset: usize },
+}
+
+impl ParseError {
+    pub fn offset(&self) -> usize {
+        match self {
+            ParseError::UnknownChar { offset, .. }
+            | ParseError::UnbalancedParen { offset }
+            | ParseError::DanglingOperator { offset, .. }
+            | ParseError::EmptyPattern { offset }
+            | ParseError::UnterminatedClass { offset }
+            | ParseError::EmptyClass { offset }
+            | ParseError::InvalidRange { offset }
+            | ParseError::InvalidRepetition { offset }
+            | ParseError::RepetitionTooLarge { offset }
+            | ParseError::InvalidEscape { offset }
+            | ParseError::PatternTooLarge { offset }
+            | ParseError::TooDeeplyNested { offset } => *offset,
+        }
+    }
+
+    /// Renders the error together with the offending pattern and a caret pointing at the
+    /// position of the error, e.g.
+    ///
+    /// ```text
+    /// error: unexpected character '#' at offset 2
+    ///   ab#c
+    ///     ^
+    /// ```
+    pub fn render(&self, input_string: &str) -> String {
+        let offset = self.offset().min(input_string.len());
+        let column = input_string[..offset].chars().count();
+        format!(
+            "error: {}\n  {}\n  {}^",
+            self,
+            input_string,
+            " ".repeat(column)
+        )
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownChar { ch, offset } => {
+                write!(f, "unexpected character '{}' at offset {}", ch, offset)
+            }
+            ParseError::UnbalancedParen { offset } => {
+                write!(f, "unbalanced parenthesis at offset {}", offset)
+            }
+            ParseError::DanglingOperator { op, offset } => {
+                write!(
+                    f,
+                    "operator '{}' at offset {} is missing an operand",
+                    op, offset
+                )
+            }
+            ParseError::EmptyPattern { offset } => {
+                write!(f, "empty pattern at offset {}", offset)
+            }
+            ParseError::UnterminatedClass { offset } => {
+                write!(f, "character class at offset {} is never closed", offset)
+            }
+            ParseError::EmptyClass { offset } => {
+                write!(f, "empty character class at offset {}", offset)
+            }
+            ParseError::InvalidRange { offset } => {
+                write!(f, "invalid character range at offset {}", offset)
+            }
+            ParseError::InvalidRepetition { offset } => {
+                write!(f, "invalid repetition at offset {}", offset)
+            }
+            ParseError::RepetitionTooLarge { offset } => {
+                write!(f, "repetition at offset {} is too large", offset)
+            }
+            ParseError::InvalidEscape { offset } => {
+                write!(f, "invalid escape sequence at offset {}", offset)
+            }
+            ParseError::PatternTooLarge { offset } => {
+                write!(f, "pattern at offset {} is too large", offset)
+            }
+            ParseError::TooDeeplyNested { offset } => {
+                write!(f, "expression at offset {} is nested too deeply", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The source spans of a parsed expression.
+///
+/// A `SpanTree` has the same shape as the `RegexExpr` it was parsed alongside: `children[i]` is
+/// the span tree of the i-th boxed sub-expression of the node, in declaration order.
+/// Spans are byte ranges into the input string. The span of a `Group` node, and of an expression
+/// inside a non-capturing group, includes the parentheses. The parser uses them to point its
+/// diagnostics about the whole expression, such as `TooDeeplyNested`, at the offending node.
+///
+/// The spans are kept apart from the `RegexExpr` because expressions are also built by hand, by
+/// `simplify` and from json, where there is no source text, and because two expressions of the
+/// same shape should compare equal with `==` wherever they were written in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanTree {
+    pub span: Range<usize>,
+    pub children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    fn leaf(span: Range<usize>) -> SpanTree {
+        SpanTree {
+            span,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Parses an input string representing a regex expression.
+/// The input string will consist of literal characters, which match themselves,
+/// as well as characters '(' ')' for grouping expressions '*' for Kleene star and '|' for OR.
+///
+/// the input "ab|c" should be read as "(ab)|c",  with concatenation having higher precedence than OR.
+/// the input "a|b*" should be read as "a|(b*)",  with kleene star   having higher precedence than OR.
+/// the input "ab*" should be read as "a(b*)",    with kleene star   having higher precedence than concatenation.
+///
+/// The input "a|b&c" should be read as "a|(b&c)", where '&' matches the strings matched by both of
+/// its operands: intersection binds more tightly than OR but less tightly than concatenation.
+/// A prefix '~' matches every string its operand does not match. It binds less tightly than the
+/// postfix operators but more tightly than concatenation, so "~a*b" is read as "(~(a*))b".
+///
+/// All binary operators are right associative: "abc" is read as "a(bc)" and "a|b|c" as "a|(b|c)".
+/// A run of more than `MAX_RUN_LENGTH` operands, such as a long literal, is joined in pieces
+/// instead, as described on `join_run`, which matches the same strings.
+///
+/// Parentheses also form a capturing group, numbered from 1 in the order of their '('.
+/// Writing "(?:" instead of "(" only groups the expression without capturing it.
+///
+/// The other postfix operators are '+' for one or more, '?' for zero or one, and "{m}", "{m,}" or
+/// "{m,n}" for bounded repetition. They all bind as tightly as '*'. A '.' matches any single
+/// character except a newline.
+///
+/// A bracket expression such as "[a-z0-9_]" matches any single character in one of the listed
+/// ranges, and "[^aeiou]" matches any single character not listed. Inside the brackets every
+/// character other than ']' and '\\' stands for itself, and a '-' is literal when it comes first or last.
+///
+/// The characters `\ . [ ] ( ) { } * + ? | & ~` must be escaped with a '\\' to be matched literally.
+/// The other escape sequences, which may also be used inside brackets, are:
+/// - "\\n", "\\r", "\\t" and "\\0" for newline, carriage return, tab and the zero character,
+/// - "\\x41" for the character with the given two hex digit code,
+/// - "\\u{1F600}" for the character with the given code of one to six hex digits,
+/// - "\\d", "\\w" and "\\s" for an ASCII digit, an ASCII word character ([0-9A-Za-z_]) and
+///   whitespace ([ \\t\\n\\x0B\\x0C\\r]), and "\\D", "\\W" and "\\S" for their negations.
+///
+/// Malformed input is reported as a `ParseError` rather than a panic. So is an expression nested
+/// more than `MAX_NESTING_DEPTH` levels deep. As a run of operands is joined in pieces, even a
+/// long literal or alternation only adds a few levels.
+pub fn parse_regex(input_string: &str) -> Result<RegexExpr, ParseError> {
+    parse_regex_with_spans(input_string).map(|(expr, _)| expr)
+}
+
+/// Same as `parse_regex`, but also returns the source span of every node of the expression.
+pub fn parse_regex_with_spans(input_string: &str) -> Result<(RegexExpr, SpanTree), ParseError> {
+    let mut parser = Parser {
+        input: input_string,
+        pos: 0,
+        num_groups: 0,
+        depth: 0,
+    };
+
+    if parser.peek().is_none() {
+        return Err(ParseError::EmptyPattern { offset: 0 });
+    }
+    let result = parser.parse_binary(0)?;
+    if parser.peek().is_some() {
+        return Err(ParseError::UnbalancedParen { offset: parser.pos });
+    }
+    // the parser only counts its own recursion, and postfix operators applied to a group add to
+    // the depth of everything inside it, so the depth of the whole tree is checked here
+    if let Some(offset) = too_deep(&result.1) {
+        return Err(ParseError::TooDeeplyNested { offset });
+    }
+    // each repetition is checked as it is parsed, but many of them together may still be too large
+    if state_count_bound(&result.0) > MAX_NFA_STATES {
+        return Err(ParseError::PatternTooLarge { offset: 0 });
+    }
+    Ok(result)
+}
+
+/// Returns the start of the outermost node nested more than `MAX_NESTING_DEPTH` levels deep in
+/// the spans of an expression, if there is one.
+///
+/// This does not recurse, so that it can be used on trees too deep to be recursed over.
+fn too_deep(spans: &SpanTree) -> Option<usize> {
+    let mut stack = vec![(spans, 1)];
+    while let Some((node, depth)) = stack.pop() {
+        if depth > MAX_NESTING_DEPTH {
+            return Some(node.span.start);
+        }
+        stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+    }
+    None
+}
+
+/// Joins a non-empty run of operands with a right associative binary operator, which `join` applies
+/// to two operands.
+///
+/// A run of at most `MAX_RUN_LENGTH` operands is joined from the right into a chain as deep as the
+/// run is long, e.g. "abc" into "a(bc)". A longer run is cut into at most `MAX_RUN_LENGTH - 1`
+/// pieces of at least `MAX_RUN_LENGTH` operands, each joined the same way, followed by its last
+/// operand, and these are joined into a chain: a literal of 40 characters becomes a piece of 32
+/// characters, a piece of 7 characters and its last character. The result is only a few levels deep
+/// however long the run is, so that it can be recursed over, and, as long as no operand of a longer
+/// run is itself joined by the operator, it prints as a pattern which is joined back the same way.
+pub fn join_run<T>(mut operands: Vec<T>, join: &impl Fn(T, T) -> T) -> T {
+    if operands.len() > MAX_RUN_LENGTH {
+        let last = operands.pop().unwrap();
+        let piece_len = operands
+            .len()
+            .div_ceil(MAX_RUN_LENGTH - 1)
+            .max(MAX_RUN_LENGTH);
+        let mut pieces = Vec::new();
+        let mut operands = operands.into_iter().peekable();
+        while operands.peek().is_some() {
+            pieces.push(join_run(operands.by_ref().take(piece_len).collect(), join));
+        }
+        pieces.push(last);
+        operands_into_chain(pieces, join)
+    } else {
+        operands_into_chain(operands, join)
+    }
+}
+
+/// Joins a non-empty list of operands from the right.
+fn operands_into_chain<T>(operands: Vec<T>, join: &impl Fn(T, T) -> T) -> T {
+    let mut operands = operands.into_iter().rev();
+    let last = operands.next().unwrap();
+    operands.fold(last, |rest, operand| join(operand, rest))
+}
+
+/// The binary operators of the regex syntax. Concatenation has no operator character; it is
+/// implied whenever one expression directly follows another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Concat,
+}
+
+impl BinaryOp {
+    /// Operators with a higher precedence bind more tightly.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Concat => 3,
+        }
+    }
+
+    fn build(self, lhs: RegexExpr, rhs: RegexExpr) -> RegexExpr {
+        match self {
+            BinaryOp::Or => RegexExpr::Or(Box::new(lhs), Box::new(rhs)),
+            BinaryOp::And => RegexExpr::And(Box::new(lhs), Box::new(rhs)),
+            BinaryOp::Concat => RegexExpr::Concat(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
+    /// Joins two operands along with their spans.
+    fn build_with_spans(
+        self,
+        (lhs, lhs_spans): (RegexExpr, SpanTree),
+        (rhs, rhs_spans): (RegexExpr, SpanTree),
+    ) -> (RegexExpr, SpanTree) {
+        let spans = SpanTree {
+            span: lhs_spans.span.start..rhs_spans.span.end,
+            children: vec![lhs_spans, rhs_spans],
+        };
+        (self.build(lhs, rhs), spans)
+    }
+
+    /// Pushes the operands of an expression joined by this operator, such as the contents of
+    /// "(?:ab)" for concatenation, or else the expression itself.
+    fn push_operands(
+        self,
+        operand: (RegexExpr, SpanTree),
+        operands: &mut Vec<(RegexExpr, SpanTree)>,
+    ) {
+        match (self, operand) {
+            (BinaryOp::Or, (RegexExpr::Or(e1, e2), spans))
+            | (BinaryOp::And, (RegexExpr::And(e1, e2), spans))
+            | (BinaryOp::Concat, (RegexExpr::Concat(e1, e2), spans)) => {
+                let [spans1, spans2]: [SpanTree; 2] = spans.children.try_into().unwrap();
+                self.push_operands((*e1, spans1), operands);
+                self.push_operands((*e2, spans2), operands);
+            }
+            (_, operand) => operands.push(operand),
+        }
+    }
+}
+
+/// A precedence-climbing parser over the input string. `pos` is the byte offset of the next
+/// character to be read, `num_groups` the number of capturing groups opened so far, and `depth`
+/// the number of nested unary operators and parentheses around the expression being parsed.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    num_groups: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Enters a unary operator or parenthesis at `offset`, failing if that nests it too deeply.
+    /// The caller leaves it again by decrementing `depth`.
+    fn enter(&mut self, offset: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(ParseError::TooDeeplyNested { offset });
+        }
+        Ok(())
+    }
+
+    /// Returns the binary operator that follows, if any, without consuming it.
+    fn peek_binary_op(&self) -> Option<BinaryOp> {
+        match self.peek()? {
+            '|' => Some(BinaryOp::Or),
+            '&' => Some(BinaryOp::And),
+            ')' | '*' | '+' | '?' | '{' => None,
+            // anything else starts another operand (or is an error that parse_atom will report)
+            _ => Some(BinaryOp::Concat),
+        }
+    }
+
+    /// Parses a sequence of operands joined by binary operators of precedence at least
+    /// `min_precedence`.
+    ///
+    /// The operands joined by the same operator are collected in a loop rather than by recursing
+    /// once per operator, so that the parser only recurses as deep as the expression is nested.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<(RegexExpr, SpanTree), ParseError> {
+        let mut lhs = self.parse_prefix()?;
+
+        while let Some(op) = self.peek_binary_op() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            // the operands of op, each binding more tightly than op
+            let mut operands = vec![lhs];
+            while self.peek_binary_op() == Some(op) {
+                let op_offset = self.pos;
+                if op != BinaryOp::Concat {
+                    let op_char = self.bump().unwrap();
+                    if matches!(self.peek(), None | Some(')')) {
+                        return Err(ParseError::DanglingOperator {
+                            op: op_char,
+                            offset: op_offset,
+                        });
+                    }
+                }
+                operands.push(self.parse_binary(op.precedence() + 1)?);
+            }
+
+            // a long run is cut into pieces, which needs operands not joined by op themselves
+            if operands.len() > MAX_RUN_LENGTH {
+                let mut flat = Vec::new();
+                for operand in operands {
+                    op.push_operands(operand, &mut flat);
+                }
+                operands = flat;
+            }
+            lhs = join_run(operands, &|lhs, rhs| op.build_with_spans(lhs, rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses any number of '~' operators applied to an atom with its postfix operators.
+    fn parse_prefix(&mut self) -> Result<(RegexExpr, SpanTree), ParseError> {
+        let start = self.pos;
+        if self.peek() != Some('~') {
+            return self.parse_postfix();
+        }
+        self.bump();
+        if matches!(self.peek(), None | Some(')' | '|' | '&')) {
+            return Err(ParseError::DanglingOperator {
+                op: '~',
+                offset: start,
+            });
+        }
+
+        self.enter(start)?;
+        let (expr, spans) = self.parse_prefix()?;
+        self.depth -= 1;
+        let spans = SpanTree {
+            span: start..spans.span.end,
+            children: vec![spans],
+        };
+        Ok((RegexExpr::Not(Box::new(expr)), spans))
+    }
+
+    /// Parses an atom followed by any number of postfix operators.
+    fn parse_postfix(&mut self) -> Result<(RegexExpr, SpanTree), ParseError> {
+        let (mut expr, mut spans) = self.parse_atom()?;
+
+        let depth = self.depth;
+        while let Some(op @ ('*' | '+' | '?' | '{')) = self.peek() {
+            let op_offset = self.pos;
+            self.enter(op_offset)?;
+            self.bump();
+            let child = Box::new(expr);
+            expr = match op {
+                '*' => RegexExpr::Star(child),
+                '+' => RegexExpr::Plus(child),
+                '?' => RegexExpr::Optional(child),
+                _ => {
+                    let (min, max) = self.parse_repetition(op_offset)?;
+                    let repeated = RegexExpr::Repeat(child, min, max);
+                    if state_count_bound(&repeated) > MAX_NFA_STATES {
+                        return Err(ParseError::RepetitionTooLarge { offset: op_offset });
+                    }
+                    repeated
+                }
+            };
+            spans = SpanTree {
+                span: spans.span.start..self.pos,
+                children: vec![spans],
+            };
+        }
+        self.depth = depth;
+
+        Ok((expr, spans))
+    }
+
+    /// Parses the rest of a repetition whose '{' was at offset `start`, returning its bounds.
+    fn parse_repetition(&mut self, start: usize) -> Result<(u32, Option<u32>), ParseError> {
+        let min = self
+            .parse_count(start)?
+            .ok_or(ParseError::InvalidRepetition { offset: start })?;
+
+        let max = match self.bump() {
+            Some('}') => return Ok((min, Some(min))),
+            Some(',') => self.parse_count(start)?,
+            _ => return Err(ParseError::InvalidRepetition { offset: start }),
+        };
+        if self.bump() != Some('}') || max.is_some_and(|max| max < min) {
+            return Err(ParseError::InvalidRepetition { offset: start });
+        }
+        Ok((min, max))
+    }
+
+    /// Parses a possibly empty decimal count inside the repetition whose '{' was at `start`.
+    fn parse_count(&mut self, start: usize) -> Result<Option<u32>, ParseError> {
+        let digits_start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        let digits = &self.input[digits_start..self.pos];
+        if digits.is_empty() {
+            return Ok(None);
+        }
+        match digits.parse::<u32>() {
+            Ok(count) if count <= MAX_REPETITION => Ok(Some(count)),
+            _ => Err(ParseError::RepetitionTooLarge { offset: start }),
+        }
+    }
+
+    /// Parses a single literal, a bracket expression or a parenthesized expression.
+    fn parse_atom(&mut self) -> Result<(RegexExpr, SpanTree), ParseError> {
+        let start = self.pos;
+        match self.bump() {
+            Some('(') => {
+                let capturing = !self.input[self.pos..].starts_with("?:");
+                let group_index = if capturing {
+                    self.num_groups += 1;
+                    self.num_groups
+                } else {
+                    self.pos += 2;
+                    0
+                };
+
+                match self.peek() {
+                    Some(')') => return Err(ParseError::EmptyPattern { offset: self.pos }),
+                    None => return Err(ParseError::UnbalancedParen { offset: start }),
+                    _ => {}
+                }
+                self.enter(start)?;
+                let (expr, mut spans) = self.parse_binary(0)?;
+                self.depth -= 1;
+                if self.bump() != Some(')') {
+                    return Err(ParseError::UnbalancedParen { offset: start });
+                }
+
+                if capturing {
+                    let group = RegexExpr::Group(Box::new(expr), group_index);
+                    spans = SpanTree {
+                        span: start..self.pos,
+                        children: vec![spans],
+                    };
+                    Ok((group, spans))
+                } else {
+                    spans.span = start..self.pos;
+                    Ok((expr, spans))
+                }
+            }
+            Some('.') => Ok((RegexExpr::AnyChar, SpanTree::leaf(start..self.pos))),
+            Some('[') => {
+                let class = self.parse_class(start)?;
+                Ok((RegexExpr::Class(class), SpanTree::leaf(start..self.pos)))
+            }
+            Some(')') => Err(ParseError::UnbalancedParen { offset: start }),
+            Some(op @ ('*' | '+' | '?' | '{' | '|' | '&')) => {
+                Err(ParseError::DanglingOperator { op, offset: start })
+            }
+            Some('\\') => {
+                let expr = match self.parse_escape(start)? {
+                    Escape::Char(c) => RegexExpr::SingleChar(c),
+                    Escape::Class(class) => RegexExpr::Class(class),
+                };
+                Ok((expr, SpanTree::leaf(start..self.pos)))
+            }
+            Some(ch @ (']' | '}')) => Err(ParseError::UnknownChar { ch, offset: start }),
+            Some(c) => Ok((RegexExpr::SingleChar(c), SpanTree::leaf(start..self.pos))),
+            None => Err(ParseError::EmptyPattern { offset: start }),
+        }
+    }
+
+    /// Parses the rest of a bracket expression whose '[' was at offset `start`.
+    fn parse_class(&mut self, start: usize) -> Result<CharClass, ParseError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.bump();
+        }
+
+        let mut ranges = Vec::new();
+        loop {
+            let offset = self.pos;
+            let low = match self.bump() {
+                None => return Err(ParseError::UnterminatedClass { offset: start }),
+                Some(']') if ranges.is_empty() => {
+                    return Err(ParseError::EmptyClass { offset: start })
+                }
+                Some(']') => break,
+                Some('\\') => match self.parse_escape(offset)? {
+                    Escape::Char(c) => c,
+                    Escape::Class(class) => {
+                        ranges.extend(class.matched_ranges());
+                        continue;
+                    }
+                },
+                Some(low) => low,
+            };
+
+            // a '-' right before the closing ']' is a literal '-'
+            let mut rest = self.input[self.pos..].chars();
+            if rest.next() == Some('-') && !matches!(rest.next(), None | Some(']')) {
+                self.bump();
+                let high_offset = self.pos;
+                let high = match self.bump() {
+                    Some('\\') => match self.parse_escape(high_offset)? {
+                        Escape::Char(c) => c,
+                        // a shorthand class can't be the end of a range
+                        Escape::Class(_) => return Err(ParseError::InvalidRange { offset }),
+                    },
+                    high => high.unwrap(),
+                };
+                if high < low {
+                    return Err(ParseError::InvalidRange { offset });
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+
+        Ok(CharClass::new(ranges, negated))
+    }
+
+    /// Parses the rest of an escape sequence whose '\\' was at offset `start`.
+    fn parse_escape(&mut self, start: usize) -> Result<Escape, ParseError> {
+        let invalid = ParseError::InvalidEscape { offset: start };
+        let c = self.bump().ok_or(invalid.clone())?;
+
+        let shorthand = |ranges: &[(char, char)], negated| {
+            Ok(Escape::Class(CharClass::new(ranges.to_vec(), negated)))
+        };
+        match c {
+            'n' => Ok(Escape::Char('\n')),
+            'r' => Ok(Escape::Char('\r')),
+            't' => Ok(Escape::Char('\t')),
+            '0' => Ok(Escape::Char('\0')),
+            'x' => {
+                let digits = self.input[self.pos..]
+                    .get(..2)
+                    .filter(|digits| is_hex(digits))
+                    .ok_or(invalid.clone())?;
+                let code = u32::from_str_radix(digits, 16).map_err(|_| invalid.clone())?;
+                self.pos += 2;
+                char::from_u32(code).map(Escape::Char).ok_or(invalid)
+            }
+            'u' => {
+                let rest = &self.input[self.pos..];
+                let digits = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(digits, _)| digits)
+                    .filter(|digits| (1..=6).contains(&digits.len()) && is_hex(digits))
+                    .ok_or(invalid.clone())?;
+                let code = u32::from_str_radix(digits, 16).map_err(|_| invalid.clone())?;
+                self.pos += digits.len() + 2;
+                char::from_u32(code).map(Escape::Char).ok_or(invalid)
+            }
+            'd' | 'D' => shorthand(DIGIT_RANGES, c == 'D'),
+            'w' | 'W' => shorthand(WORD_RANGES, c == 'W'),
+            's' | 'S' => shorthand(SPACE_RANGES, c == 'S'),
+            c if c.is_alphanumeric() => Err(invalid),
+            // any other character, e.g. an operator, stands for itself
+            c => Ok(Escape::Char(c)),
+        }
+    }
+}
+
+fn is_hex(digits: &str) -> bool {
+    digits.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// What an escape sequence stands for.
+enum Escape {
+    Char(char),
+    Class(CharClass),
+}
+
+const DIGIT_RANGES: &[(char, char)] = &[('0', '9')];
+const WORD_RANGES: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE_RANGES: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
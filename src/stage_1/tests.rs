@@ -1,9 +1,12 @@
-use crate::{parse_regex, RegexExpr};
+#![cfg(test)]
+
+use super::{parse_regex, parse_regex_with_spans, ParseError, SpanTree, MAX_RUN_LENGTH};
+use crate::{CharClass, RegexExpr};
 
 #[test]
 fn test_parse_regex1() {
     let expected_expr = RegexExpr::SingleChar('a');
-    assert_eq!(parse_regex("a"), expected_expr);
+    assert_eq!(parse_regex("a").unwrap(), expected_expr);
 }
 
 #[test]
@@ -12,7 +15,7 @@ fn test_parse_regex2() {
         Box::new(RegexExpr::SingleChar('a')),
         Box::new(RegexExpr::SingleChar('b')),
     );
-    assert_eq!(parse_regex("ab"), expected_expr);
+    assert_eq!(parse_regex("ab").unwrap(), expected_expr);
 }
 
 #[test]
@@ -21,15 +24,26 @@ fn test_parse_regex3() {
         Box::new(RegexExpr::SingleChar('a')),
         Box::new(RegexExpr::SingleChar('b')),
     );
-    assert_eq!(parse_regex("a|b"), expected_expr);
-    assert_eq!(parse_regex("(a)|b"), expected_expr);
-    assert_eq!(parse_regex("((a)|(((b))))"), expected_expr);
+    assert_eq!(parse_regex("a|b").unwrap(), expected_expr);
+    // plain parentheses capture, so "(a)|b" no longer parses to the same expression as "a|b"
+    assert_eq!(
+        parse_regex("(a)|b").unwrap(),
+        RegexExpr::Or(
+            Box::new(RegexExpr::Group(Box::new(RegexExpr::SingleChar('a')), 1)),
+            Box::new(RegexExpr::SingleChar('b')),
+        )
+    );
+    assert_eq!(parse_regex("(?:a)|b").unwrap(), expected_expr);
+    assert_eq!(
+        parse_regex("(?:(?:a)|(?:(?:(?:b))))").unwrap(),
+        expected_expr
+    );
 }
 
 #[test]
 fn test_parse_regex4() {
     let expected_expr = RegexExpr::Star(Box::new(RegexExpr::SingleChar('a')));
-    assert_eq!(parse_regex("a*"), expected_expr);
+    assert_eq!(parse_regex("a*").unwrap(), expected_expr);
 }
 
 #[test]
@@ -37,9 +51,9 @@ fn test_parse_regex5() {
     let e1 = RegexExpr::Star(Box::new(RegexExpr::SingleChar('a')));
     let expected_expr = RegexExpr::Or(Box::new(e1), Box::new(RegexExpr::SingleChar('b')));
 
-    assert_eq!(parse_regex("a*|b"), expected_expr);
-    assert_eq!(parse_regex("(a*)|b"), expected_expr);
-    assert_eq!(parse_regex("a*|(b)"), expected_expr);
+    assert_eq!(parse_regex("a*|b").unwrap(), expected_expr);
+    assert_eq!(parse_regex("(?:a*)|b").unwrap(), expected_expr);
+    assert_eq!(parse_regex("a*|(?:b)").unwrap(), expected_expr);
 }
 
 #[test]
@@ -47,8 +61,8 @@ fn test_parse_regex6() {
     let e1 = RegexExpr::Star(Box::new(RegexExpr::SingleChar('b')));
     let expected_expr = RegexExpr::Or(Box::new(RegexExpr::SingleChar('a')), Box::new(e1));
 
-    assert_eq!(parse_regex("a|b*"), expected_expr);
-    assert_eq!(parse_regex("a|(b*)"), expected_expr);
+    assert_eq!(parse_regex("a|b*").unwrap(), expected_expr);
+    assert_eq!(parse_regex("a|(?:b*)").unwrap(), expected_expr);
 }
 
 #[test]
@@ -59,7 +73,579 @@ fn test_parse_regex7() {
     );
     let expected_expr = RegexExpr::Or(Box::new(e1), Box::new(RegexExpr::SingleChar('c')));
 
-    assert_eq!(parse_regex("ab|c"), expected_expr);
-    assert_eq!(parse_regex("ab|(c)"), expected_expr);
-    assert_eq!(parse_regex("(ab)|c"), expected_expr);
+    assert_eq!(parse_regex("ab|c").unwrap(), expected_expr);
+    assert_eq!(parse_regex("ab|(?:c)").unwrap(), expected_expr);
+    assert_eq!(parse_regex("(?:ab)|c").unwrap(), expected_expr);
+}
+
+#[test]
+fn test_parse_regex_errors() {
+    assert_eq!(
+        parse_regex("ab]c"),
+        Err(ParseError::UnknownChar { ch: ']', offset: 2 })
+    );
+    assert_eq!(parse_regex(""), Err(ParseError::EmptyPattern { offset: 0 }));
+    assert_eq!(
+        parse_regex("a()"),
+        Err(ParseError::EmptyPattern { offset: 2 })
+    );
+    assert_eq!(
+        parse_regex("(ab"),
+        Err(ParseError::UnbalancedParen { offset: 0 })
+    );
+    assert_eq!(
+        parse_regex("ab)c"),
+        Err(ParseError::UnbalancedParen { offset: 2 })
+    );
+    assert_eq!(
+        parse_regex("a("),
+        Err(ParseError::UnbalancedParen { offset: 1 })
+    );
+    assert_eq!(
+        parse_regex(")"),
+        Err(ParseError::UnbalancedParen { offset: 0 })
+    );
+}
+
+#[test]
+fn test_parse_regex_nesting_limit() {
+    // the parser recurses at every level, and a thousand levels need more than the stack of a test
+    // thread in a debug build
+    std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(check_nesting_limit)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn check_nesting_limit() {
+    let nested = |open: &str, depth: usize| format!("{}a{}", open.repeat(depth), ")".repeat(depth));
+    assert!(parse_regex(&nested("(?:", 1000)).is_ok());
+    assert_eq!(
+        parse_regex(&nested("(?:", 1001)),
+        Err(ParseError::TooDeeplyNested { offset: 3000 })
+    );
+    // every capturing group is a level of the expression around the 'a'
+    assert!(parse_regex(&nested("(", 999)).is_ok());
+    assert_eq!(
+        parse_regex(&nested("(", 1000)),
+        Err(ParseError::TooDeeplyNested { offset: 1000 })
+    );
+
+    assert_eq!(
+        parse_regex(&format!("{}a", "~".repeat(1001))),
+        Err(ParseError::TooDeeplyNested { offset: 1000 })
+    );
+    assert_eq!(
+        parse_regex(&format!("a{}", "*".repeat(1001))),
+        Err(ParseError::TooDeeplyNested { offset: 1001 })
+    );
+
+    // a run of operands is joined in pieces, so however long it is it is only a few levels deep
+    assert!(parse_regex(&"a".repeat(1001)).is_ok());
+    assert!(parse_regex(&format!("{}a{}", "(".repeat(900), ")".repeat(900))).is_ok());
+    assert!(parse_regex(&format!(
+        "{}{}{}",
+        "(".repeat(900),
+        "a".repeat(5000),
+        ")".repeat(900)
+    ))
+    .is_ok());
+
+    // the parser is never more than 70 levels deep, but the expression is 1240 levels deep
+    let pattern = (0..40).fold("a".to_string(), |inner, _| {
+        format!("(?:{}){}", inner, "*".repeat(30))
+    });
+    assert!(matches!(
+        parse_regex(&pattern),
+        Err(ParseError::TooDeeplyNested { .. })
+    ));
+}
+
+#[test]
+fn test_parse_regex_long_runs() {
+    // the run is cut into pieces, each joined from the right
+    let literal = "abcdefghijklmnopqrstuvwxyz".repeat(1000);
+    let expr = parse_regex(&literal).unwrap();
+    assert_eq!(
+        expr.to_string().replace("(?:", "").replace(')', ""),
+        literal
+    );
+    assert_eq!(parse_regex(&expr.to_string()), Ok(expr));
+
+    let alternation = (0..3000)
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join("|");
+    let expr = parse_regex(&alternation).unwrap();
+    assert_eq!(parse_regex(&expr.to_string()), Ok(expr));
+
+    // a run of no more than MAX_RUN_LENGTH operands is a single chain
+    let chain = |n: usize| {
+        (1..n).fold(RegexExpr::SingleChar('a'), |rest, _| {
+            RegexExpr::Concat(Box::new(RegexExpr::SingleChar('a')), Box::new(rest))
+        })
+    };
+    assert_eq!(
+        parse_regex(&"a".repeat(MAX_RUN_LENGTH)),
+        Ok(chain(MAX_RUN_LENGTH))
+    );
+    assert_eq!(
+        parse_regex(&"a".repeat(MAX_RUN_LENGTH + 1)),
+        Ok(RegexExpr::Concat(
+            Box::new(chain(MAX_RUN_LENGTH)),
+            Box::new(RegexExpr::SingleChar('a'))
+        ))
+    );
+    // the operands of a long run are the operands of the groups in it
+    assert_eq!(
+        parse_regex(&format!("(?:aa){}", "a".repeat(MAX_RUN_LENGTH))),
+        parse_regex(&"a".repeat(MAX_RUN_LENGTH + 2))
+    );
+}
+
+#[test]
+fn test_parse_regex_dangling_operators() {
+    assert_eq!(
+        parse_regex("*"),
+        Err(ParseError::DanglingOperator { op: '*', offset: 0 })
+    );
+    assert_eq!(
+        parse_regex("a|"),
+        Err(ParseError::DanglingOperator { op: '|', offset: 1 })
+    );
+    assert_eq!(
+        parse_regex("|a"),
+        Err(ParseError::DanglingOperator { op: '|', offset: 0 })
+    );
+    assert_eq!(
+        parse_regex("a(|b)"),
+        Err(ParseError::DanglingOperator { op: '|', offset: 2 })
+    );
+    assert_eq!(
+        parse_regex("(a|)b"),
+        Err(ParseError::DanglingOperator { op: '|', offset: 2 })
+    );
+    assert_eq!(
+        parse_regex("a|*b"),
+        Err(ParseError::DanglingOperator { op: '*', offset: 2 })
+    );
+}
+
+#[test]
+fn test_parse_error_render() {
+    let err = parse_regex("ab}c").unwrap_err();
+    assert_eq!(
+        err.render("ab}c"),
+        "error: unexpected character '}' at offset 2\n  ab}c\n    ^"
+    );
+
+    // the caret is placed by character, not by byte
+    let err = parse_regex("éé|").unwrap_err();
+    assert_eq!(err.offset(), 4);
+    assert!(err.render("éé|").ends_with("\n    ^"));
+}
+
+#[test]
+fn test_parse_regex_associativity() {
+    let a = || Box::new(RegexExpr::SingleChar('a'));
+    let b = || Box::new(RegexExpr::SingleChar('b'));
+    let c = || Box::new(RegexExpr::SingleChar('c'));
+
+    let expected_expr = RegexExpr::Concat(a(), Box::new(RegexExpr::Concat(b(), c())));
+    assert_eq!(parse_regex("abc").unwrap(), expected_expr);
+
+    let expected_expr = RegexExpr::Or(a(), Box::new(RegexExpr::Or(b(), c())));
+    assert_eq!(parse_regex("a|b|c").unwrap(), expected_expr);
+
+    let expected_expr = RegexExpr::Concat(
+        a(),
+        Box::new(RegexExpr::Star(Box::new(RegexExpr::Star(b())))),
+    );
+    assert_eq!(parse_regex("ab**").unwrap(), expected_expr);
+}
+
+#[test]
+fn test_parse_regex_spans() {
+    // "(?:ab)*|c" is Or(Star(Concat(a, b)), c)
+    let (_, spans) = parse_regex_with_spans("(?:ab)*|c").unwrap();
+    let expected_spans = SpanTree {
+        span: 0..9,
+        children: vec![
+            SpanTree {
+                span: 0..7,
+                children: vec![SpanTree {
+                    span: 0..6,
+                    children: vec![
+                        SpanTree {
+                            span: 3..4,
+                            children: vec![],
+                        },
+                        SpanTree {
+                            span: 4..5,
+                            children: vec![],
+                        },
+                    ],
+                }],
+            },
+            SpanTree {
+                span: 8..9,
+                children: vec![],
+            },
+        ],
+    };
+    assert_eq!(spans, expected_spans);
+
+    // a capturing group is a node of its own
+    let (_, spans) = parse_regex_with_spans("x(ab)").unwrap();
+    assert_eq!(spans.children[1].span, 1..5);
+    assert_eq!(spans.children[1].children[0].span, 2..4);
+
+    // spans are byte offsets
+    let (_, spans) = parse_regex_with_spans("éa").unwrap();
+    assert_eq!(spans.span, 0..3);
+    assert_eq!(spans.children[1].span, 2..3);
+}
+
+#[test]
+fn test_parse_class() {
+    let expected_expr = RegexExpr::Class(CharClass::new(
+        vec![('a', 'z'), ('0', '9'), ('_', '_')],
+        false,
+    ));
+    assert_eq!(parse_regex("[a-z0-9_]").unwrap(), expected_expr);
+    assert_eq!(parse_regex("[_0-9a-z]").unwrap(), expected_expr);
+    // overlapping and adjacent ranges are merged
+    assert_eq!(
+        parse_regex("[a-mn-zd-f]").unwrap(),
+        RegexExpr::Class(CharClass::new(vec![('a', 'z')], false))
+    );
+
+    let expected_expr = RegexExpr::Concat(
+        Box::new(RegexExpr::Class(CharClass::new(
+            vec![('a', 'a'), ('e', 'e'), ('i', 'i'), ('o', 'o'), ('u', 'u')],
+            true,
+        ))),
+        Box::new(RegexExpr::Star(Box::new(RegexExpr::SingleChar('x')))),
+    );
+    assert_eq!(parse_regex("[^aeiou]x*").unwrap(), expected_expr);
+
+    // '-' is literal at either end, and other operators are literal inside brackets
+    assert_eq!(
+        parse_regex("[-a]").unwrap(),
+        RegexExpr::Class(CharClass::new(vec![('-', '-'), ('a', 'a')], false))
+    );
+    assert_eq!(
+        parse_regex("[a-]").unwrap(),
+        RegexExpr::Class(CharClass::new(vec![('-', '-'), ('a', 'a')], false))
+    );
+    assert_eq!(
+        parse_regex("[*|(]").unwrap(),
+        RegexExpr::Class(CharClass::new(
+            vec![('(', '('), ('*', '*'), ('|', '|')],
+            false
+        ))
+    );
+}
+
+#[test]
+fn test_parse_class_errors() {
+    assert_eq!(
+        parse_regex("a[bc"),
+        Err(ParseError::UnterminatedClass { offset: 1 })
+    );
+    assert_eq!(
+        parse_regex("a[^"),
+        Err(ParseError::UnterminatedClass { offset: 1 })
+    );
+    assert_eq!(parse_regex("[]"), Err(ParseError::EmptyClass { offset: 0 }));
+    assert_eq!(
+        parse_regex("x[^]"),
+        Err(ParseError::EmptyClass { offset: 1 })
+    );
+    assert_eq!(
+        parse_regex("[az-a]"),
+        Err(ParseError::InvalidRange { offset: 2 })
+    );
+}
+
+#[test]
+fn test_parse_postfix_operators() {
+    let a = || Box::new(RegexExpr::SingleChar('a'));
+
+    assert_eq!(parse_regex("a+").unwrap(), RegexExpr::Plus(a()));
+    assert_eq!(parse_regex("a?").unwrap(), RegexExpr::Optional(a()));
+    assert_eq!(
+        parse_regex("a{3}").unwrap(),
+        RegexExpr::Repeat(a(), 3, Some(3))
+    );
+    assert_eq!(
+        parse_regex("a{2,}").unwrap(),
+        RegexExpr::Repeat(a(), 2, None)
+    );
+    assert_eq!(
+        parse_regex("a{0,12}").unwrap(),
+        RegexExpr::Repeat(a(), 0, Some(12))
+    );
+
+    // postfix operators bind more tightly than concatenation and stack on each other
+    let expected_expr = RegexExpr::Concat(
+        Box::new(RegexExpr::AnyChar),
+        Box::new(RegexExpr::Star(Box::new(RegexExpr::Optional(a())))),
+    );
+    assert_eq!(parse_regex(".a?*").unwrap(), expected_expr);
+
+    let expected_expr = RegexExpr::Or(
+        Box::new(RegexExpr::Plus(Box::new(RegexExpr::Concat(a(), a())))),
+        Box::new(RegexExpr::AnyChar),
+    );
+    assert_eq!(parse_regex("(?:aa)+|.").unwrap(), expected_expr);
+}
+
+#[test]
+fn test_parse_repetition_errors() {
+    assert_eq!(
+        parse_regex("+a"),
+        Err(ParseError::DanglingOperator { op: '+', offset: 0 })
+    );
+    assert_eq!(
+        parse_regex("a|?"),
+        Err(ParseError::DanglingOperator { op: '?', offset: 2 })
+    );
+    for pattern in [
+        "a{", "a{}", "a{,3}", "a{3", "a{3,", "a{3,2}", "a{x}", "a{1,2,3}",
+    ] {
+        assert_eq!(
+            parse_regex(pattern),
+            Err(ParseError::InvalidRepetition { offset: 1 }),
+            "{}",
+            pattern
+        );
+    }
+
+    assert_eq!(
+        parse_regex("a{1001}"),
+        Err(ParseError::RepetitionTooLarge { offset: 1 })
+    );
+    assert_eq!(
+        parse_regex("a{99999999999}"),
+        Err(ParseError::RepetitionTooLarge { offset: 1 })
+    );
+    // every repetition is small on its own, but together they would build a huge nfa
+    assert_eq!(
+        parse_regex("((a{1000}){1000})"),
+        Err(ParseError::RepetitionTooLarge { offset: 10 })
+    );
+    // every repetition is small enough, but the whole pattern is not
+    assert_eq!(
+        parse_regex(&"a{1000}".repeat(50)),
+        Err(ParseError::PatternTooLarge { offset: 0 })
+    );
+    assert!(parse_regex(&"a{1000}".repeat(49)).is_ok());
+}
+
+#[test]
+fn test_parse_literals_and_escapes() {
+    let single = |c| Box::new(RegexExpr::SingleChar(c));
+
+    // punctuation and spaces are literals
+    assert_eq!(
+        parse_regex("a-@ ").unwrap(),
+        RegexExpr::Concat(
+            single('a'),
+            Box::new(RegexExpr::Concat(
+                single('-'),
+                Box::new(RegexExpr::Concat(single('@'), single(' ')))
+            ))
+        )
+    );
+
+    // escaped operators are literals
+    assert_eq!(
+        parse_regex("\\*\\(").unwrap(),
+        RegexExpr::Concat(single('*'), single('('))
+    );
+    for (pattern, c) in [
+        ("\\\\", '\\'),
+        ("\\.", '.'),
+        ("\\]", ']'),
+        ("\\n", '\n'),
+        ("\\t", '\t'),
+        ("\\x41", 'A'),
+        ("\\u{1F600}", '\u{1F600}'),
+        ("\\u{e9}", 'é'),
+    ] {
+        assert_eq!(parse_regex(pattern).unwrap(), RegexExpr::SingleChar(c));
+    }
+    assert_eq!(parse_regex("\\x41+").unwrap(), RegexExpr::Plus(single('A')));
+}
+
+#[test]
+fn test_parse_shorthand_classes() {
+    assert_eq!(
+        parse_regex("\\d").unwrap(),
+        RegexExpr::Class(CharClass::new(vec![('0', '9')], false))
+    );
+    assert_eq!(
+        parse_regex("\\W").unwrap(),
+        RegexExpr::Class(CharClass::new(
+            vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+            true
+        ))
+    );
+    assert_eq!(
+        parse_regex("\\s").unwrap(),
+        RegexExpr::Class(CharClass::new(vec![('\t', '\r'), (' ', ' ')], false))
+    );
+
+    // escapes inside brackets
+    assert_eq!(
+        parse_regex("[\\d\\-.\\]]").unwrap(),
+        RegexExpr::Class(CharClass::new(
+            vec![('0', '9'), ('-', '.'), (']', ']')],
+            false
+        ))
+    );
+    assert_eq!(
+        parse_regex("[\\x00-\\x1f]").unwrap(),
+        RegexExpr::Class(CharClass::new(vec![('\0', '\u{1f}')], false))
+    );
+}
+
+#[test]
+fn test_parse_escape_errors() {
+    for pattern in [
+        "\\",
+        "\\q",
+        "\\x4",
+        "\\xg1",
+        "\\x+1",
+        "\\u41",
+        "\\u{}",
+        "\\u{1234567}",
+        "\\u{d800}",
+        "\\u{41",
+    ] {
+        assert_eq!(
+            parse_regex(pattern),
+            Err(ParseError::InvalidEscape { offset: 0 }),
+            "{}",
+            pattern
+        );
+    }
+    assert_eq!(
+        parse_regex("[a\\q]"),
+        Err(ParseError::InvalidEscape { offset: 2 })
+    );
+    assert_eq!(
+        parse_regex("[a-\\d]"),
+        Err(ParseError::InvalidRange { offset: 1 })
+    );
+}
+
+#[test]
+fn test_parse_groups() {
+    let single = |c| Box::new(RegexExpr::SingleChar(c));
+
+    assert_eq!(
+        parse_regex("(a)b").unwrap(),
+        RegexExpr::Concat(Box::new(RegexExpr::Group(single('a'), 1)), single('b'))
+    );
+
+    // groups are numbered in the order of their opening parenthesis, skipping non-capturing ones
+    let expected_expr = RegexExpr::Group(
+        Box::new(RegexExpr::Or(
+            Box::new(RegexExpr::Group(single('a'), 2)),
+            Box::new(RegexExpr::Star(Box::new(RegexExpr::Group(single('b'), 3)))),
+        )),
+        1,
+    );
+    assert_eq!(parse_regex("((a)|(b)*)").unwrap(), expected_expr);
+    assert_eq!(parse_regex("((a)|(?:(b))*)").unwrap(), expected_expr);
+
+    assert_eq!(
+        parse_regex("(?:)"),
+        Err(ParseError::EmptyPattern { offset: 3 })
+    );
+    assert_eq!(
+        parse_regex("(?x)"),
+        Err(ParseError::DanglingOperator { op: '?', offset: 1 })
+    );
+}
+
+#[test]
+fn test_parse_boolean_operators() {
+    let a = || Box::new(RegexExpr::SingleChar('a'));
+    let b = || Box::new(RegexExpr::SingleChar('b'));
+    let c = || Box::new(RegexExpr::SingleChar('c'));
+
+    // '&' binds more tightly than '|' and less tightly than concatenation
+    let expected_expr = RegexExpr::Or(a(), Box::new(RegexExpr::And(b(), c())));
+    assert_eq!(parse_regex("a|b&c").unwrap(), expected_expr);
+    let expected_expr = RegexExpr::And(Box::new(RegexExpr::Concat(a(), b())), c());
+    assert_eq!(parse_regex("ab&c").unwrap(), expected_expr);
+    let expected_expr = RegexExpr::And(a(), Box::new(RegexExpr::And(b(), c())));
+    assert_eq!(parse_regex("a&b&c").unwrap(), expected_expr);
+
+    // '~' binds less tightly than the postfix operators and more tightly than concatenation
+    let expected_expr = RegexExpr::Concat(
+        Box::new(RegexExpr::Not(Box::new(RegexExpr::Star(a())))),
+        b(),
+    );
+    assert_eq!(parse_regex("~a*b").unwrap(), expected_expr);
+    let expected_expr = RegexExpr::Not(Box::new(RegexExpr::Not(a())));
+    assert_eq!(parse_regex("~~a").unwrap(), expected_expr);
+    let expected_expr = RegexExpr::And(a(), Box::new(RegexExpr::Not(b())));
+    assert_eq!(parse_regex("a&~b").unwrap(), expected_expr);
+
+    // escaped, both are literals
+    let expected_expr = RegexExpr::Concat(
+        Box::new(RegexExpr::SingleChar('&')),
+        Box::new(RegexExpr::SingleChar('~')),
+    );
+    assert_eq!(parse_regex("\\&\\~").unwrap(), expected_expr);
+
+    let (_, spans) = parse_regex_with_spans("x&~ab").unwrap();
+    assert_eq!(spans.children[1].span, 2..5);
+    assert_eq!(spans.children[1].children[0].span, 2..4);
+    assert_eq!(spans.children[1].children[0].children[0].span, 3..4);
+}
+
+#[test]
+fn test_parse_boolean_operator_errors() {
+    assert_eq!(
+        parse_regex("a&"),
+        Err(ParseError::DanglingOperator { op: '&', offset: 1 })
+    );
+    assert_eq!(
+        parse_regex("&a"),
+        Err(ParseError::DanglingOperator { op: '&', offset: 0 })
+    );
+    assert_eq!(
+        parse_regex("(a&)"),
+        Err(ParseError::DanglingOperator { op: '&', offset: 2 })
+    );
+    assert_eq!(
+        parse_regex("a~"),
+        Err(ParseError::DanglingOperator { op: '~', offset: 1 })
+    );
+    assert_eq!(
+        parse_regex("~|a"),
+        Err(ParseError::DanglingOperator { op: '~', offset: 0 })
+    );
+    assert_eq!(
+        parse_regex("~*"),
+        Err(ParseError::DanglingOperator { op: '*', offset: 1 })
+    );
+
+    // the nfa of a small operand is small, so many of them are not too large
+    for pattern in [
+        "~a~b~c~d~e~f~g~h~i~j",
+        "(~a){10}",
+        "(?:a&b){100}",
+        "(?:\\w+&~(.*x.*)){3}",
+    ] {
+        assert!(parse_regex(pattern).is_ok(), "{}", pattern);
+    }
 }
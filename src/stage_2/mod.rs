@@ -1,194 +1,309 @@
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
 mod tests;
 
-use crate::{Nfa, RegexExpr, State};
+use crate::{
+    dfa::{DfaError, DEFAULT_STATE_LIMIT},
+    language::{complement, intersect},
+    CharClass, Label, Nfa, RegexExpr, State,
+};
 
+/// The largest count allowed in a bounded repetition `{m,n}`.
+pub const MAX_REPETITION: u32 = 1000;
+
+/// The largest number of states `convert_regex_to_nfa` may be asked to build.
+/// The parser rejects any expression whose `state_count_bound` exceeds this.
+pub const MAX_NFA_STATES: usize = 100_000;
+
+/// Converts an expression into a NFA accepting the same strings.
+///
+/// # Panics
+///
+/// Panics if the expression contains an `And` or `Not` whose operands need a dfa of more than
+/// `DEFAULT_STATE_LIMIT` states. Use `try_convert_regex_to_nfa` for expressions from user input.
 pub fn convert_regex_to_nfa(expression: &RegexExpr) -> Nfa {
+    try_convert_regex_to_nfa(expression).expect("the expression needs too large a dfa")
+}
+
+/// Converts an expression into a NFA accepting the same strings.
+///
+/// The operands of `And` and `Not` are determinized to build their product and complement dfas,
+/// which fails if one of those dfas would have more than `DEFAULT_STATE_LIMIT` states. The result
+/// of such an operator is a minimal dfa converted back by `dfa_to_nfa`, so capturing groups inside
+/// it never participate in a match.
+pub fn try_convert_regex_to_nfa(expression: &RegexExpr) -> Result<Nfa, DfaError> {
+    let mut builder = NfaBuilder::default();
+    let (start, end) = (builder.new_state(), builder.new_state());
+    builder.build(expression, start, end)?;
+    Ok(builder.finish(start, end))
+}
+
+/// Returns a nfa accepting every string which ends with a string accepted by `m`, i.e. the nfa of
+/// ".*m" where '.' also matches a newline. A dfa built from it can search for m in a single pass.
+pub fn unanchored_nfa(m: &Nfa) -> Nfa {
+    let any_char = RegexExpr::Class(CharClass::new(vec![('\0', char::MAX)], false));
+    let mut builder = NfaBuilder::default();
+    let (start, middle, end) = (
+        builder.new_state(),
+        builder.new_state(),
+        builder.new_state(),
+    );
+    builder
+        .build(&RegexExpr::Star(Box::new(any_char)), start, middle)
+        .expect("a star of a class needs no dfa");
+    builder.embed(m, middle, end);
+    builder.finish(start, end)
+}
+
+/// Returns an upper bound on the number of states of `convert_regex_to_nfa(expression)`,
+/// saturating at `usize::MAX`.
+pub fn state_count_bound(expression: &RegexExpr) -> usize {
     match expression {
-        RegexExpr::SingleChar(c) => {
-            let transitions = HashMap::from([(0, vec![(*c, 1)])]);
-            Nfa {
-                initial_state: 0,
-                accepting_state: 1,
-                transitions,
-            }
+        RegexExpr::SingleChar(_) | RegexExpr::Class(_) | RegexExpr::AnyChar => 2,
+        RegexExpr::Star(e) => state_count_bound(e).saturating_add(2),
+        RegexExpr::Plus(e) => state_count_bound(e).saturating_mul(2).saturating_add(1),
+        RegexExpr::Optional(e) => state_count_bound(e),
+        RegexExpr::Group(e, _) => state_count_bound(e).saturating_add(2),
+        RegexExpr::Repeat(e, min, max) => {
+            let copies = max.unwrap_or(*min + 1) as usize;
+            state_count_bound(e)
+                .saturating_mul(copies)
+                .saturating_add(2)
         }
-        RegexExpr::Star(e) => {
-            let mut m = convert_regex_to_nfa(e);
-            let num_states = rename_states(&mut m);
-
-            let old_acc_state = m.accepting_state;
-            let old_init_state = m.initial_state;
-            let new_init_state = num_states;
-            let new_acc_state = num_states + 1;
-
-            m.initial_state = new_init_state;
-            m.accepting_state = new_acc_state;
-            m.transitions.insert(
-                old_acc_state,
-                vec![('\0', old_init_state), ('\0', new_acc_state)],
-            );
-            m.transitions.insert(
-                new_init_state,
-                vec![('\0', old_init_state), ('\0', new_acc_state)],
-            );
-
-            rename_states(&mut m);
-            return m;
+        RegexExpr::Concat(e1, e2) | RegexExpr::Or(e1, e2) => {
+            state_count_bound(e1).saturating_add(state_count_bound(e2))
         }
-        RegexExpr::Concat(e1, e2) => {
-            let mut m1 = convert_regex_to_nfa(e1);
-            let mut m2 = convert_regex_to_nfa(e2);
-            rename_nfa_states(&mut m1, &mut m2);
-
-            // we can get rid of (forget) the accepting state of m1, and then redirect all
-            // transitions into m1's accepting state into m2's initial state
-            let m1_acc_state = m1.accepting_state;
-            let m2_init_state = m2.initial_state;
-
-            let mut resulting_nfa = Nfa {
-                initial_state: m1.initial_state,
-                accepting_state: m2.accepting_state,
-                transitions: HashMap::new(),
-            };
-            for (s, out_transitions) in m1.transitions {
-                // the transitions in m1 might need to be renamed.
-                resulting_nfa.transitions.insert(
-                    s,
-                    out_transitions
-                        .iter()
-                        .map(|(c, new_s)| {
-                            (
-                                *c,
-                                if *new_s != m1_acc_state {
-                                    *new_s
-                                } else {
-                                    m2_init_state
-                                },
-                            )
-                        })
-                        .collect(),
-                );
+        // the operands are built on their own and determinized, and the result is a dfa with a
+        // new initial and accepting state
+        RegexExpr::And(e1, e2) => dfa_state_count_bound(e1)
+            .saturating_mul(dfa_state_count_bound(e2))
+            .min(DEFAULT_STATE_LIMIT)
+            .saturating_add(2),
+        RegexExpr::Not(e) => dfa_state_count_bound(e).saturating_add(2),
+    }
+}
+
+/// Returns an upper bound on the number of states of the dfa determinized from
+/// `convert_regex_to_nfa(expression)`, which is never more than `DEFAULT_STATE_LIMIT`.
+fn dfa_state_count_bound(expression: &RegexExpr) -> usize {
+    // every state of the dfa is a set of states of the nfa
+    let num_states = state_count_bound(expression);
+    if num_states < usize::BITS as usize {
+        (1 << num_states).min(DEFAULT_STATE_LIMIT)
+    } else {
+        DEFAULT_STATE_LIMIT
+    }
+}
+
+/// Builds a nfa in place: every sub-expression is given the states it starts and ends at, and
+/// adds its transitions between them, so no part of the nfa is ever renamed while it is built.
+///
+/// The start state given to a sub-expression never gets incoming transitions from it, and the
+/// end state never gets outgoing ones, which lets the states be shared between neighbours.
+#[derive(Default)]
+struct NfaBuilder {
+    num_states: usize,
+    transitions: HashMap<State, Vec<(Label, State)>>,
+}
+
+impl NfaBuilder {
+    fn new_state(&mut self) -> State {
+        self.num_states += 1;
+        self.num_states - 1
+    }
+
+    fn add_transition(&mut self, from: State, label: Label, to: State) {
+        self.transitions.entry(from).or_default().push((label, to));
+    }
+
+    /// Adds the transitions of a nfa accepting the strings of `expression` from `start` to `end`.
+    fn build(&mut self, expression: &RegexExpr, start: State, end: State) -> Result<(), DfaError> {
+        match expression {
+            RegexExpr::SingleChar(c) => self.add_transition(start, Label::Char(*c), end),
+            RegexExpr::Class(class) => {
+                // the whole class becomes a single range-labelled transition
+                self.add_transition(start, Label::Class(class.matched_ranges()), end)
             }
-            for (s, out_transitions) in m2.transitions {
-                resulting_nfa.transitions.insert(s, out_transitions);
+            RegexExpr::AnyChar => {
+                // '.' matches any char except newline
+                let label = Label::Class(vec![('\0', '\u{9}'), ('\u{b}', char::MAX)]);
+                self.add_transition(start, label, end)
             }
-
-            rename_states(&mut resulting_nfa);
-            return resulting_nfa;
-        }
-        RegexExpr::Or(e1, e2) => {
-            let mut m1 = convert_regex_to_nfa(e1);
-            let mut m2 = convert_regex_to_nfa(e2);
-            rename_nfa_states(&mut m1, &mut m2);
-
-            // we can get rid of (forget) the accepting state and initial state of m1, and then
-            // rename the transitions in m1 to use the accepting and initial states of m2
-            let m1_acc_state = m1.accepting_state;
-            let m1_init_state = m1.initial_state;
-
-            let mut resulting_nfa = Nfa {
-                initial_state: m2.initial_state,
-                accepting_state: m2.accepting_state,
-                transitions: HashMap::new(),
-            };
-
-            for (s, out_transitions) in m1.transitions {
-                // the transitions in m1 might need to be renamed.
-                let s = if s != m1_init_state {
-                    s
-                } else {
-                    m2.initial_state
-                };
-                resulting_nfa.transitions.insert(
-                    s,
-                    out_transitions
-                        .iter()
-                        .map(|(c, new_s)| {
-                            (
-                                *c,
-                                if *new_s != m1_acc_state {
-                                    *new_s
-                                } else {
-                                    m2.accepting_state
-                                },
-                            )
-                        })
-                        .collect(),
-                );
+            RegexExpr::Star(e) => {
+                let (inner_start, inner_end) = (self.new_state(), self.new_state());
+                self.add_star_transitions(start, inner_start, inner_end, end);
+                self.build(e, inner_start, inner_end)?;
             }
-            for (s, out_transitions) in m2.transitions {
-                if !resulting_nfa.transitions.contains_key(&s) {
-                    resulting_nfa.transitions.insert(s, out_transitions);
-                } else {
-                    resulting_nfa
-                        .transitions
-                        .get_mut(&s)
-                        .unwrap()
-                        .extend(out_transitions);
+            RegexExpr::Plus(e) => {
+                // e+ is e(e*)
+                let m = try_convert_regex_to_nfa(e)?;
+                let middle = self.new_state();
+                self.embed(&m, start, middle);
+                self.embed_star(&m, middle, end);
+            }
+            RegexExpr::Optional(e) => {
+                self.build(e, start, end)?;
+                // nothing leads back into start, so this transition can only be taken before
+                // reading anything
+                self.add_transition(start, Label::Epsilon, end);
+            }
+            RegexExpr::Repeat(e, min, max) => {
+                // e{m,n} is m copies of e followed by (n-m) nested optional copies of e, i.e.
+                // e{2,4} is ee(e(e)?)?, and e{m,} is m copies of e followed by e*.
+                // e is built once, and every copy is the same nfa with its states offset.
+                let m = try_convert_regex_to_nfa(e)?;
+                let copies = max.unwrap_or(*min);
+                let mut copy_start = start;
+                for i in 0..copies {
+                    let copy_end = if i + 1 == copies && max.is_some() {
+                        end
+                    } else {
+                        self.new_state()
+                    };
+                    self.embed(&m, copy_start, copy_end);
+                    if i >= *min {
+                        self.add_transition(copy_start, Label::Epsilon, end);
+                    }
+                    copy_start = copy_end;
+                }
+                match max {
+                    None => self.embed_star(&m, copy_start, end),
+                    Some(0) => self.add_transition(start, Label::Epsilon, end),
+                    Some(_) => {}
                 }
             }
-
-            rename_states(&mut resulting_nfa);
-            return resulting_nfa;
+            RegexExpr::Concat(e1, e2) => {
+                let middle = self.new_state();
+                self.build(e1, start, middle)?;
+                self.build(e2, middle, end)?;
+            }
+            RegexExpr::Or(e1, e2) => {
+                // both alternatives share the start and end states
+                self.build(e1, start, end)?;
+                self.build(e2, start, end)?;
+            }
+            RegexExpr::Group(e, index) => {
+                // the group is delimited by transitions saving its start and end positions
+                let (inner_start, inner_end) = (self.new_state(), self.new_state());
+                self.add_transition(start, Label::Save(2 * index), inner_start);
+                self.build(e, inner_start, inner_end)?;
+                self.add_transition(inner_end, Label::Save(2 * index + 1), end);
+            }
+            RegexExpr::And(e1, e2) => {
+                let m = intersect(
+                    &try_convert_regex_to_nfa(e1)?,
+                    &try_convert_regex_to_nfa(e2)?,
+                )?;
+                self.embed(&m, start, end);
+            }
+            RegexExpr::Not(e) => {
+                let m = complement(&try_convert_regex_to_nfa(e)?)?;
+                self.embed(&m, start, end);
+            }
         }
+        Ok(())
     }
-}
 
-/// if m1 has n_1 states and m2 has n_2 states then the result of calling this function
-/// is that m1 should have states named 0, 1, 2, ..., (n_1-1) and m2 should have states
-/// named n_1, n_1+1, ..., n_1+n_2-1
-///
-/// This ensures that the two nfas have distinct state names.
-fn rename_nfa_states(m1: &mut Nfa, m2: &mut Nfa) {
-    let mut next_state: State = 0;
-    let mut m1_rename_map: HashMap<State, State> = HashMap::new();
-    let mut m2_rename_map: HashMap<State, State> = HashMap::new();
+    /// Adds a copy of `m` from `start` to `end`, offsetting the ids of its other states past the
+    /// states built so far.
+    fn embed(&mut self, m: &Nfa, start: State, end: State) {
+        let offset = self.num_states;
+        let rename = |q: State| match q {
+            q if q == m.initial_state => start,
+            q if q == m.accepting_state => end,
+            q => offset + q,
+        };
 
-    // use the get_all_state_references function to construct the two rename_maps.
-    for s in get_all_state_references(m1) {
-        if !m1_rename_map.contains_key(&s) {
-            m1_rename_map.insert(s, next_state);
-            next_state += 1;
+        let mut max_state = m.initial_state.max(m.accepting_state);
+        for (q, transitions) in &m.transitions {
+            for (label, next) in transitions {
+                max_state = max_state.max(*q).max(*next);
+                self.add_transition(rename(*q), label.clone(), rename(*next));
+            }
         }
+        self.num_states += max_state + 1;
     }
-    for s in get_all_state_references(m2) {
-        if !m2_rename_map.contains_key(&s) {
-            m2_rename_map.insert(s, next_state);
-            next_state += 1;
-        }
+
+    /// Adds the transitions of the star of `m` from `start` to `end`.
+    fn embed_star(&mut self, m: &Nfa, start: State, end: State) {
+        let (inner_start, inner_end) = (self.new_state(), self.new_state());
+        self.add_star_transitions(start, inner_start, inner_end, end);
+        self.embed(m, inner_start, inner_end);
     }
 
-    *m1 = rename_nfa_with_map(m1, &m1_rename_map);
-    *m2 = rename_nfa_with_map(m2, &m2_rename_map);
+    /// Adds the epsilon transitions of a star from `start` to `end` around the nfa which goes from
+    /// `inner_start` to `inner_end`.
+    fn add_star_transitions(
+        &mut self,
+        start: State,
+        inner_start: State,
+        inner_end: State,
+        end: State,
+    ) {
+        self.add_transition(start, Label::Epsilon, inner_start);
+        self.add_transition(start, Label::Epsilon, end);
+        self.add_transition(inner_end, Label::Epsilon, inner_start);
+        self.add_transition(inner_end, Label::Epsilon, end);
+    }
+
+    /// Returns the nfa built from `start` to `end`, with its states renamed by `rename_states`.
+    fn finish(self, start: State, end: State) -> Nfa {
+        let mut m = Nfa {
+            initial_state: start,
+            accepting_state: end,
+            transitions: self.transitions,
+        };
+        rename_states(&mut m);
+        m
+    }
 }
 
 /// If m has n unique states, then this function renames the states 0, 1, ..., (n-1)
 /// and also returns n
+///
+/// The numbering is canonical: states are numbered in the order of a breadth first search from
+/// the initial state (which becomes 0), following the transitions of each state in order, and the
+/// accepting state comes last. So the same NFA is always numbered the same way, whatever the
+/// order of its `HashMap` of transitions.
 pub fn rename_states(m: &mut Nfa) -> usize {
     let mut next_state: State = 0;
     let mut rename_map: HashMap<State, State> = HashMap::new();
     for s in get_all_state_references(m) {
-        if !rename_map.contains_key(&s) {
-            rename_map.insert(s, next_state);
+        if let Entry::Vacant(entry) = rename_map.entry(s) {
+            entry.insert(next_state);
             next_state += 1;
         }
     }
 
     *m = rename_nfa_with_map(m, &rename_map);
 
-    let num_total_states = rename_map.len();
-    return num_total_states;
+    rename_map.len()
 }
 
+/// Returns every state of m once, in breadth first order from the initial state with the
+/// accepting state last. States which cannot be reached from the initial state come after the
+/// others, searched from in increasing order.
+///
+/// The accepting state is searched from like any other, in case it has transitions of its own
+/// (or is the initial state), and only put last at the end.
 fn get_all_state_references(m: &Nfa) -> Vec<State> {
+    let mut unreachable: Vec<State> = m.transitions.keys().copied().collect();
+    unreachable.sort_unstable();
+
     let mut states = Vec::new();
-    states.push(m.initial_state);
-    for (q, out_transitions) in m.transitions.iter() {
-        states.push(*q);
-        for (transition_char, next_state) in out_transitions {
-            if *next_state != m.accepting_state {
-                states.push(*next_state);
+    let mut seen: HashSet<State> = HashSet::new();
+    for root in std::iter::once(m.initial_state).chain(unreachable) {
+        if !seen.insert(root) {
+            continue;
+        }
+        let mut queue = VecDeque::from([root]);
+        while let Some(q) = queue.pop_front() {
+            if q != m.accepting_state {
+                states.push(q);
+            }
+            for (_, next_state) in m.transitions.get(&q).into_iter().flatten() {
+                if seen.insert(*next_state) {
+                    queue.push_back(*next_state);
+                }
             }
         }
     }
@@ -208,8 +323,8 @@ fn rename_nfa_with_map(m: &Nfa, rename_map: &HashMap<State, State>) -> Nfa {
         let new_state = *rename_map.get(state).unwrap();
         let new_outgoing_transitions = transitions
             .iter()
-            .map(|(c, s)| (*c, *rename_map.get(s).unwrap()))
-            .collect::<Vec<(char, State)>>();
+            .map(|(c, s)| (c.clone(), *rename_map.get(s).unwrap()))
+            .collect::<Vec<(Label, State)>>();
         new_m
             .transitions
             .insert(new_state, new_outgoing_transitions);
@@ -1,8 +1,10 @@
 #![cfg(test)]
 
-use crate::RegexExpr;
+use std::collections::HashMap;
 
-use super::{convert_regex_to_nfa, rename_nfa_states};
+use crate::{stage_1::parse_regex, stage_3::run_nfa, CharClass, Label, Nfa, RegexExpr};
+
+use super::{convert_regex_to_nfa, rename_states, state_count_bound};
 
 #[test]
 fn test_generate_simple_nfa() {
@@ -12,15 +14,43 @@ fn test_generate_simple_nfa() {
 }
 
 #[test]
-fn test_rename_nfas() {
-    let mut m1 = convert_regex_to_nfa(&RegexExpr::SingleChar('a'));
-    let mut m2 = convert_regex_to_nfa(&RegexExpr::SingleChar('b'));
+fn test_generate_repeat_nfa() {
+    // the copies of "ab" share their end and start states
+    let mut m = convert_regex_to_nfa(&parse_regex("(?:ab){3}").unwrap());
+    assert_eq!(rename_states(&mut m), 7);
+    assert!(run_nfa(&m, "ababab"));
+    assert!(!run_nfa(&m, "abab"));
+
+    let m = convert_regex_to_nfa(&parse_regex("(?:ab){1,3}c{2,}").unwrap());
+    for (input, expected) in [
+        ("abcc", true),
+        ("ababcccc", true),
+        ("abababcc", true),
+        ("ababababcc", false),
+        ("abc", false),
+        ("cc", false),
+    ] {
+        assert_eq!(run_nfa(&m, input), expected, "{:?}", input);
+    }
 
-    rename_nfa_states(&mut m1, &mut m2);
+    // the copies of a large repetition are built without renaming the nfa for every one
+    let pattern = format!("(?:{}){{900}}", "abcdefghijklmnopqrstuvwxyz".repeat(2));
+    let mut m = convert_regex_to_nfa(&parse_regex(&pattern).unwrap());
+    assert_eq!(rename_states(&mut m), 52 * 900 + 1);
+}
 
-    println!("This test generated 2 simple nfas and then renamed them:");
-    println!("{:?}", m1);
-    println!("{:?}", m2);
+#[test]
+fn test_generate_nested_group_nfa() {
+    // the save transitions are added around the inner nfa without renaming it
+    let expr = (1..=500)
+        .rev()
+        .fold(RegexExpr::SingleChar('a'), |e, index| {
+            RegexExpr::Group(Box::new(e), index)
+        });
+    let mut m = convert_regex_to_nfa(&expr);
+    assert_eq!(rename_states(&mut m), 2 * 500 + 2);
+    assert_eq!(m.transitions[&0], vec![(Label::Save(2), 1)]);
+    assert!(run_nfa(&m, "a"));
 }
 
 #[test]
@@ -85,3 +115,135 @@ fn test_generate_nfa4() {
     println!("Here is a nfa which accepts the string '(a*) | b': ");
     println!("{:?}", m);
 }
+
+#[test]
+fn test_generate_class_nfa() {
+    let expr = RegexExpr::Class(CharClass::new(vec![('a', 'z'), ('0', '9')], false));
+    let m = convert_regex_to_nfa(&expr);
+
+    // the whole class is a single transition labelled with its ranges
+    let expected_label = Label::Class(vec![('0', '9'), ('a', 'z')]);
+    assert_eq!(
+        m.transitions.get(&m.initial_state),
+        Some(&vec![(expected_label, m.accepting_state)])
+    );
+
+    let expr = RegexExpr::Class(CharClass::new(vec![('b', 'y')], true));
+    let m = convert_regex_to_nfa(&expr);
+    let expected_label = Label::Class(vec![('\0', 'a'), ('z', char::MAX)]);
+    assert_eq!(
+        m.transitions.get(&m.initial_state),
+        Some(&vec![(expected_label, m.accepting_state)])
+    );
+}
+
+#[test]
+fn test_state_count_bound() {
+    for pattern in [
+        "a",
+        "a*",
+        "ab|c",
+        "a+",
+        "(ab)?",
+        "a{3}",
+        "a{2,5}",
+        "(a|b){2,}",
+        ".{0}",
+        "a&b*",
+        "~(ab)",
+        "~a~b~c",
+        "(~a){3}",
+        "(?:a&b|c){2}",
+        "~(.*admin.*)",
+        "~(a&b)*",
+    ] {
+        let expr = parse_regex(pattern).unwrap();
+        let mut m = convert_regex_to_nfa(&expr);
+        let num_states = rename_states(&mut m);
+        assert!(num_states <= state_count_bound(&expr), "{}", pattern);
+    }
+}
+
+#[test]
+fn test_generate_boolean_nfa() {
+    let m = convert_regex_to_nfa(&parse_regex("\\w+&~(.*admin.*)").unwrap());
+    for (input, expected) in [
+        ("user42", true),
+        ("superadmin", false),
+        ("admin_", false),
+        ("adm1n", true),
+        ("", false),
+        ("hello world", false),
+    ] {
+        assert_eq!(run_nfa(&m, input), expected, "{:?}", input);
+    }
+
+    // the operands may be combined with the other constructions
+    let m = convert_regex_to_nfa(&parse_regex("(~a)b|c&[a-c]").unwrap());
+    for (input, expected) in [
+        ("b", true),
+        ("bb", true),
+        ("ab", false),
+        ("c", true),
+        ("a", false),
+    ] {
+        assert_eq!(run_nfa(&m, input), expected, "{:?}", input);
+    }
+}
+
+#[test]
+fn test_rename_states_is_canonical() {
+    let mut m = convert_regex_to_nfa(&parse_regex("ab").unwrap());
+    assert_eq!(rename_states(&mut m), 3);
+    assert_eq!((m.initial_state, m.accepting_state), (0, 2));
+    assert_eq!(m.transitions[&0], vec![(Label::Char('a'), 1)]);
+    assert_eq!(m.transitions[&1], vec![(Label::Char('b'), 2)]);
+
+    // however the states are named, they are renamed the same way
+    for pattern in ["(a|b)*abb", "x(y|z)+|w?", "~(a*)&[ab]{2}"] {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        let shifted = Nfa {
+            initial_state: m.initial_state * 7 + 1000,
+            accepting_state: m.accepting_state * 7 + 1000,
+            transitions: m
+                .transitions
+                .iter()
+                .map(|(q, t)| {
+                    let t = t.iter().map(|(l, next)| (l.clone(), next * 7 + 1000));
+                    (q * 7 + 1000, t.collect())
+                })
+                .collect(),
+        };
+        let (mut m, mut shifted) = (m, shifted);
+        let num_states = rename_states(&mut m);
+        assert_eq!(rename_states(&mut shifted), num_states);
+        assert_eq!(m.transitions, shifted.transitions, "{}", pattern);
+        assert_eq!((m.initial_state, m.accepting_state), (0, num_states - 1));
+    }
+}
+
+#[test]
+fn test_rename_states_follows_the_accepting_state() {
+    // state 7 has no transitions of its own, and is only reached from the accepting state
+    let mut m = Nfa {
+        initial_state: 3,
+        accepting_state: 5,
+        transitions: HashMap::from([
+            (3, vec![(Label::Char('a'), 5)]),
+            (5, vec![(Label::Char('b'), 7)]),
+        ]),
+    };
+    assert_eq!(rename_states(&mut m), 3);
+    assert_eq!((m.initial_state, m.accepting_state), (0, 2));
+    assert_eq!(m.transitions[&2], vec![(Label::Char('b'), 1)]);
+
+    // the search also starts from an initial state which is accepting
+    let mut m = Nfa {
+        initial_state: 4,
+        accepting_state: 4,
+        transitions: HashMap::from([(4, vec![(Label::Char('a'), 9)])]),
+    };
+    assert_eq!(rename_states(&mut m), 2);
+    assert_eq!((m.initial_state, m.accepting_state), (1, 1));
+    assert_eq!(m.transitions[&1], vec![(Label::Char('a'), 0)]);
+}
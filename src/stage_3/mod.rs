@@ -1,26 +1,407 @@
 mod tests;
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
 
-use crate::{Nfa, State};
+use crate::{Label, Nfa, State};
 
 /// Runs a NFA on an input string.
 /// returns true if the NFA accepts the input string, and false otherwise.
 pub fn run_nfa(nfa: &Nfa, input_string: &str) -> bool {
     let mut curr_states: HashSet<State> = HashSet::new();
     curr_states.insert(nfa.initial_state);
-    do_epsilon_closure(&mut curr_states, &nfa);
+    do_epsilon_closure(&mut curr_states, nfa);
 
     for char in input_string.chars() {
-        do_transition(&mut curr_states, &nfa, char);
-        do_epsilon_closure(&mut curr_states, &nfa);
+        do_transition(&mut curr_states, nfa, char);
+        do_epsilon_closure(&mut curr_states, nfa);
     }
 
-    return curr_states.contains(&nfa.accepting_state);
+    curr_states.contains(&nfa.accepting_state)
+}
+
+/// Runs a NFA on every substring of an input string at once.
+/// returns true if the NFA accepts some substring of the input string (like `grep`), and false otherwise.
+///
+/// Rather than restarting the NFA at every position, the initial state is added to the set of
+/// current states before each character is read, so the whole search is a single pass.
+pub fn search_nfa(nfa: &Nfa, input_string: &str) -> bool {
+    let mut curr_states: HashSet<State> = HashSet::new();
+
+    for char in input_string.chars() {
+        curr_states.insert(nfa.initial_state);
+        do_epsilon_closure(&mut curr_states, nfa);
+        if curr_states.contains(&nfa.accepting_state) {
+            return true;
+        }
+        do_transition(&mut curr_states, nfa, char);
+    }
+
+    curr_states.insert(nfa.initial_state);
+    do_epsilon_closure(&mut curr_states, nfa);
+    curr_states.contains(&nfa.accepting_state)
+}
+
+/// Finds the first match of a NFA in an input string and returns its byte range.
+///
+/// Matches follow leftmost-longest (POSIX) semantics: among all substrings accepted by the NFA,
+/// the one starting earliest is chosen, and among those the longest. The returned match may be
+/// empty if the NFA accepts the empty string.
+pub fn find(nfa: &Nfa, input_string: &str) -> Option<Range<usize>> {
+    find_at(nfa, input_string, 0)
+}
+
+/// Returns an iterator over all non-overlapping matches of a NFA in an input string, from left
+/// to right, each chosen with the leftmost-longest semantics of `find`.
+///
+/// After an empty match the search resumes one character later, and an empty match directly at
+/// the end of the previous match is never reported, so e.g. "a*" finds "aa" and "" (at offset 3)
+/// in "aab", and not an additional empty match at offset 2.
+pub fn find_iter<'a>(nfa: &'a Nfa, input_string: &'a str) -> FindIter<'a> {
+    FindIter {
+        nfa,
+        input_string,
+        cursor: MatchCursor::default(),
+    }
+}
+
+pub struct FindIter<'a> {
+    nfa: &'a Nfa,
+    input_string: &'a str,
+    cursor: MatchCursor,
+}
+
+impl<'a> Iterator for FindIter<'a> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        while !self.cursor.is_done(self.input_string) {
+            let found = find_at(self.nfa, self.input_string, self.cursor.pos)?;
+            if self.cursor.advance(self.input_string, &found) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+/// The progress of an iteration over non-overlapping matches, shared by `FindIter` and `CapturesIter`.
+#[derive(Default)]
+struct MatchCursor {
+    /// The byte offset at which the next search starts.
+    pos: usize,
+    last_match_end: Option<usize>,
+}
+
+impl MatchCursor {
+    fn is_done(&self, input_string: &str) -> bool {
+        self.pos > input_string.len()
+    }
+
+    /// Moves the cursor past a match found by searching from `self.pos`. Returns false if the
+    /// match is an empty match directly at the end of the previous match, which is skipped.
+    fn advance(&mut self, input_string: &str, found: &Range<usize>) -> bool {
+        // the position right after the match, stepping over one character if it is empty
+        self.pos = if found.is_empty() {
+            found.end + next_char_len(input_string, found.end)
+        } else {
+            found.end
+        };
+
+        if found.is_empty() && self.last_match_end == Some(found.start) {
+            return false;
+        }
+        self.last_match_end = Some(found.end);
+        true
+    }
+}
+
+/// The byte length of the character starting at byte offset `pos`, or 1 at the end of the string.
+fn next_char_len(input_string: &str, pos: usize) -> usize {
+    input_string[pos..]
+        .chars()
+        .next()
+        .map_or(1, |c| c.len_utf8())
+}
+
+/// Finds the leftmost-longest match which starts at or after byte offset `start`.
+///
+/// This is a single simulation pass in which every current state remembers the earliest position
+/// at which a run reaching it started. A new run is started at every position until the first
+/// match is seen; after that only runs which started no later than the best match are kept,
+/// since only they can still produce a match which is further left or longer.
+fn find_at(nfa: &Nfa, input_string: &str, start: usize) -> Option<Range<usize>> {
+    let mut curr_states: HashMap<State, usize> = HashMap::new();
+    let mut best: Option<Range<usize>> = None;
+
+    let positions = input_string[start..]
+        .char_indices()
+        .map(|(i, c)| (start + i, Some(c)))
+        .chain(std::iter::once((input_string.len(), None)));
+
+    for (pos, char) in positions {
+        if best.is_none() {
+            // an existing run in the initial state started earlier, so it takes priority
+            curr_states.entry(nfa.initial_state).or_insert(pos);
+        }
+        do_epsilon_closure_with_starts(&mut curr_states, nfa);
+
+        if let Some(&match_start) = curr_states.get(&nfa.accepting_state) {
+            let is_better = match &best {
+                None => true,
+                Some(best) => {
+                    match_start < best.start || (match_start == best.start && pos > best.end)
+                }
+            };
+            if is_better {
+                best = Some(match_start..pos);
+            }
+        }
+        if let Some(best) = &best {
+            curr_states.retain(|_, run_start| *run_start <= best.start);
+            if curr_states.is_empty() {
+                break;
+            }
+        }
+
+        if let Some(char) = char {
+            let mut next_states: HashMap<State, usize> = HashMap::new();
+            for (s, run_start) in curr_states.iter() {
+                for (label, next_state) in nfa.transitions.get(s).unwrap_or(&Vec::new()) {
+                    if label.matches(char) {
+                        let entry = next_states.entry(*next_state).or_insert(*run_start);
+                        *entry = (*entry).min(*run_start);
+                    }
+                }
+            }
+            curr_states = next_states;
+        }
+    }
+
+    best
+}
+
+/// Like `do_epsilon_closure`, but every state reached also records the earliest start position
+/// of the states it was reached from.
+fn do_epsilon_closure_with_starts(states: &mut HashMap<State, usize>, nfa: &Nfa) {
+    let mut states_to_visit: VecDeque<State> = states.keys().copied().collect();
+
+    while let Some(s) = states_to_visit.pop_front() {
+        let run_start = states[&s];
+        for (label, next_state) in nfa.transitions.get(&s).unwrap_or(&Vec::new()) {
+            if !label.is_epsilon() {
+                continue;
+            }
+            match states.get(next_state) {
+                Some(next_start) if *next_start <= run_start => {}
+                _ => {
+                    states.insert(*next_state, run_start);
+                    states_to_visit.push_back(*next_state);
+                }
+            }
+        }
+    }
+}
+
+/// The spans of a match and its capture groups: `captures[0]` is the byte range of the whole match
+/// and `captures[k]` the byte range of capture group k, or None if the group took no part in the match.
+pub type Captures = Vec<Option<Range<usize>>>;
+
+/// Finds the first match of a NFA in an input string like `find` does, along with the spans of its
+/// capture groups.
+///
+/// This is a Pike VM: a simulation in which every run (thread) carries its own copy of the capture
+/// slots, and the threads are kept in priority order. The span of the whole match follows the
+/// leftmost-longest semantics of `find`. Among all runs producing that span, the capture groups are
+/// taken from the run of highest priority, in which the left side of an '|' is preferred over the
+/// right, and '*', '+', '?' and "{m,n}" prefer to repeat once more over stopping.
+pub fn captures(nfa: &Nfa, input_string: &str) -> Option<Captures> {
+    captures_at(nfa, input_string, 0)
+}
+
+/// Returns an iterator over the captures of all non-overlapping matches of a NFA in an input
+/// string, which are the same matches `find_iter` returns.
+pub fn captures_iter<'a>(nfa: &'a Nfa, input_string: &'a str) -> CapturesIter<'a> {
+    CapturesIter {
+        nfa,
+        input_string,
+        cursor: MatchCursor::default(),
+    }
+}
+
+pub struct CapturesIter<'a> {
+    nfa: &'a Nfa,
+    input_string: &'a str,
+    cursor: MatchCursor,
+}
+
+impl<'a> Iterator for CapturesIter<'a> {
+    type Item = Captures;
+
+    fn next(&mut self) -> Option<Captures> {
+        while !self.cursor.is_done(self.input_string) {
+            let captures = captures_at(self.nfa, self.input_string, self.cursor.pos)?;
+            let found = captures[0].clone().unwrap();
+            if self.cursor.advance(self.input_string, &found) {
+                return Some(captures);
+            }
+        }
+        None
+    }
+}
+
+/// Returns the number of capture groups of a NFA, including group 0 for the whole match.
+pub fn num_capture_groups(nfa: &Nfa) -> usize {
+    nfa.transitions
+        .values()
+        .flatten()
+        .filter_map(|(label, _)| match label {
+            Label::Save(slot) => Some(slot / 2 + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+/// A Pike VM thread: a run which is about to try transition number `transition` out of `state`.
+struct Thread {
+    state: State,
+    transition: usize,
+    /// The position at which the run started.
+    start: usize,
+    slots: Vec<Option<usize>>,
+}
+
+/// The best match found so far by the Pike VM, with the capture slots of the run which found it.
+type PikeMatch = (Range<usize>, Vec<Option<usize>>);
+
+/// Finds the captures of the leftmost-longest match which starts at or after byte offset `start`.
+fn captures_at(nfa: &Nfa, input_string: &str, start: usize) -> Option<Captures> {
+    let num_slots = 2 * num_capture_groups(nfa);
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut visited: HashSet<State> = HashSet::new();
+    let mut best: Option<PikeMatch> = None;
+
+    let positions = input_string[start..]
+        .char_indices()
+        .map(|(i, c)| (start + i, Some(c)))
+        .chain(std::iter::once((input_string.len(), None)));
+
+    for (pos, char) in positions {
+        if best.is_none() {
+            // a new run has the lowest priority of all, since every other run started earlier
+            let slots = vec![None; num_slots];
+            let new_run = (nfa.initial_state, pos, slots);
+            add_thread(nfa, &mut threads, &mut visited, new_run, pos, &mut best);
+        }
+        if let Some((best_span, _)) = &best {
+            threads.retain(|thread| thread.start <= best_span.start);
+        }
+        let Some(char) = char else { break };
+        if threads.is_empty() && best.is_some() {
+            break;
+        }
+
+        let next_pos = pos + char.len_utf8();
+        let mut next_threads: Vec<Thread> = Vec::new();
+        let mut next_visited: HashSet<State> = HashSet::new();
+        for thread in threads {
+            let (label, next_state) = &nfa.transitions[&thread.state][thread.transition];
+            if label.matches(char) {
+                let run = (*next_state, thread.start, thread.slots);
+                add_thread(
+                    nfa,
+                    &mut next_threads,
+                    &mut next_visited,
+                    run,
+                    next_pos,
+                    &mut best,
+                );
+            }
+        }
+        threads = next_threads;
+        visited = next_visited;
+    }
+
+    let (span, slots) = best?;
+    let mut captures = vec![Some(span)];
+    for group in 1..num_slots / 2 {
+        captures.push(match (slots[2 * group], slots[2 * group + 1]) {
+            (Some(group_start), Some(group_end)) if group_start <= group_end => {
+                Some(group_start..group_end)
+            }
+            _ => None,
+        });
+    }
+    Some(captures)
+}
+
+/// Follows the epsilon transitions of a run `(state, start, slots)` at input position `pos` in
+/// priority order, i.e. depth first and in the order of each state's transitions, and appends a
+/// thread for every non-epsilon transition reached to `threads`.
+/// States already in `visited` were reached by a run of higher priority and are skipped.
+fn add_thread(
+    nfa: &Nfa,
+    threads: &mut Vec<Thread>,
+    visited: &mut HashSet<State>,
+    run: (State, usize, Vec<Option<usize>>),
+    pos: usize,
+    best: &mut Option<PikeMatch>,
+) {
+    enum Work {
+        Visit(State, Vec<Option<usize>>),
+        Try(State, usize, Vec<Option<usize>>),
+    }
+
+    let (state, start, slots) = run;
+    let mut stack = vec![Work::Visit(state, slots)];
+    while let Some(work) = stack.pop() {
+        let (s, slots) = match work {
+            Work::Try(s, transition, slots) => {
+                threads.push(Thread {
+                    state: s,
+                    transition,
+                    start,
+                    slots,
+                });
+                continue;
+            }
+            Work::Visit(s, slots) => (s, slots),
+        };
+        if !visited.insert(s) {
+            continue;
+        }
+
+        if s == nfa.accepting_state {
+            let is_better = match best {
+                None => true,
+                Some((best_span, _)) => {
+                    start < best_span.start || (start == best_span.start && pos > best_span.end)
+                }
+            };
+            if is_better {
+                *best = Some((start..pos, slots.clone()));
+            }
+        }
+
+        // pushed in reverse, so that the first transition is handled first
+        let out_transitions = nfa.transitions.get(&s).map_or(&[][..], |t| &t[..]);
+        for (transition, (label, next_state)) in out_transitions.iter().enumerate().rev() {
+            match label {
+                Label::Epsilon => stack.push(Work::Visit(*next_state, slots.clone())),
+                Label::Save(slot) => {
+                    let mut slots = slots.clone();
+                    slots[*slot] = Some(pos);
+                    stack.push(Work::Visit(*next_state, slots));
+                }
+                _ => stack.push(Work::Try(s, transition, slots.clone())),
+            }
+        }
+    }
 }
 
 // Updates `states` to be the epsilon closure of `states`.
-fn do_epsilon_closure(states: &mut HashSet<State>, nfa: &Nfa) {
+pub fn do_epsilon_closure(states: &mut HashSet<State>, nfa: &Nfa) {
     let mut states_to_visit: VecDeque<State> = VecDeque::new();
     for s in states.iter() {
         states_to_visit.push_back(*s);
@@ -34,7 +415,7 @@ fn do_epsilon_closure(states: &mut HashSet<State>, nfa: &Nfa) {
             .get(&s)
             .unwrap_or(&Vec::new())
             .iter()
-            .filter(|(c, _)| *c == '\0')
+            .filter(|(c, _)| c.is_epsilon())
             .map(|(c, s)| *s)
             .collect::<Vec<State>>();
 
@@ -47,16 +428,16 @@ fn do_epsilon_closure(states: &mut HashSet<State>, nfa: &Nfa) {
     }
 }
 
-fn do_transition(states: &mut HashSet<State>, nfa: &Nfa, next_char: char) {
+pub fn do_transition(states: &mut HashSet<State>, nfa: &Nfa, next_char: char) {
     let mut new_states: HashSet<State> = HashSet::new();
 
     for s in states.iter() {
         let reachable_states = nfa
             .transitions
-            .get(&s)
+            .get(s)
             .unwrap_or(&Vec::new())
             .iter()
-            .filter(|(c, _)| *c == next_char)
+            .filter(|(c, _)| c.matches(next_char))
             .map(|(c, s)| *s)
             .collect::<Vec<State>>();
         new_states.extend(reachable_states);
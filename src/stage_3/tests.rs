@@ -1,7 +1,7 @@
 #![cfg(test)]
 
-use super::run_nfa;
-use crate::{stage_2::convert_regex_to_nfa, RegexExpr};
+use super::{captures, captures_iter, find, find_iter, num_capture_groups, run_nfa, search_nfa};
+use crate::{stage_1::parse_regex, stage_2::convert_regex_to_nfa, RegexExpr};
 
 #[test]
 fn test_run_nfa1() {
@@ -82,3 +82,223 @@ fn test_run_nfa4() {
     assert!(!run_nfa(&m, "bccd"));
     assert!(!run_nfa(&m, "bbcd"));
 }
+
+#[test]
+fn test_run_nfa_classes() {
+    let m = convert_regex_to_nfa(&parse_regex("[a-z_][a-z0-9_]*").unwrap());
+
+    assert!(run_nfa(&m, "x"));
+    assert!(run_nfa(&m, "snake_case_9"));
+    assert!(run_nfa(&m, "_0"));
+
+    assert!(!run_nfa(&m, ""));
+    assert!(!run_nfa(&m, "9lives"));
+    assert!(!run_nfa(&m, "camelCase"));
+
+    let m = convert_regex_to_nfa(&parse_regex("[^aeiou]*").unwrap());
+
+    assert!(run_nfa(&m, ""));
+    assert!(run_nfa(&m, "rhythm"));
+    assert!(run_nfa(&m, "XYZ \u{1F600}"));
+    assert!(!run_nfa(&m, "rhythms and blues"));
+}
+
+#[test]
+fn test_run_nfa_repetition() {
+    let m = convert_regex_to_nfa(&parse_regex("ab+c?").unwrap());
+    assert!(run_nfa(&m, "ab"));
+    assert!(run_nfa(&m, "abbbc"));
+    assert!(!run_nfa(&m, "a"));
+    assert!(!run_nfa(&m, "ac"));
+    assert!(!run_nfa(&m, "abcc"));
+
+    let m = convert_regex_to_nfa(&parse_regex("a{2,4}").unwrap());
+    assert!(!run_nfa(&m, "a"));
+    assert!(run_nfa(&m, "aa"));
+    assert!(run_nfa(&m, "aaa"));
+    assert!(run_nfa(&m, "aaaa"));
+    assert!(!run_nfa(&m, "aaaaa"));
+
+    let m = convert_regex_to_nfa(&parse_regex("(ab){2,}").unwrap());
+    assert!(!run_nfa(&m, "ab"));
+    assert!(run_nfa(&m, "abab"));
+    assert!(run_nfa(&m, "abababab"));
+    assert!(!run_nfa(&m, "ababa"));
+
+    let m = convert_regex_to_nfa(&parse_regex("xa{0}y").unwrap());
+    assert!(run_nfa(&m, "xy"));
+    assert!(!run_nfa(&m, "xay"));
+}
+
+#[test]
+fn test_run_nfa_any_char() {
+    let m = convert_regex_to_nfa(&parse_regex("a.c").unwrap());
+    assert!(run_nfa(&m, "abc"));
+    assert!(run_nfa(&m, "a c"));
+    assert!(run_nfa(&m, "a\u{1F600}c"));
+    assert!(!run_nfa(&m, "a\nc"));
+    assert!(!run_nfa(&m, "ac"));
+}
+
+#[test]
+fn test_run_nfa_escapes() {
+    let m = convert_regex_to_nfa(&parse_regex("\\w+@\\w+\\.com").unwrap());
+    assert!(run_nfa(&m, "user_1@example.com"));
+    assert!(!run_nfa(&m, "user_1@examplexcom"));
+    assert!(!run_nfa(&m, "user 1@example.com"));
+
+    let m = convert_regex_to_nfa(&parse_regex("\\d{3}-\\d{4}\\s*").unwrap());
+    assert!(run_nfa(&m, "555-1234"));
+    assert!(run_nfa(&m, "555-1234 \t "));
+    assert!(!run_nfa(&m, "555-12345"));
+
+    let m = convert_regex_to_nfa(&parse_regex("\\(\\S+\\)").unwrap());
+    assert!(run_nfa(&m, "(\u{1F600})"));
+    assert!(!run_nfa(&m, "( )"));
+}
+
+#[test]
+fn test_search_nfa() {
+    let m = convert_regex_to_nfa(&parse_regex("ab+").unwrap());
+
+    assert!(search_nfa(&m, "ab"));
+    assert!(search_nfa(&m, "xxabbbyy"));
+    assert!(search_nfa(&m, "aaab"));
+    assert!(search_nfa(&m, "cab"));
+    assert!(!search_nfa(&m, ""));
+    assert!(!search_nfa(&m, "a b"));
+    assert!(!search_nfa(&m, "ba"));
+
+    // a pattern accepting the empty string matches every input
+    let m = convert_regex_to_nfa(&parse_regex("z*").unwrap());
+    assert!(search_nfa(&m, ""));
+    assert!(search_nfa(&m, "abc"));
+
+    // the match may end exactly at the end of the input
+    let m = convert_regex_to_nfa(&parse_regex("\\d\\d").unwrap());
+    assert!(search_nfa(&m, "year 99"));
+    assert!(!search_nfa(&m, "year 9"));
+}
+
+#[test]
+fn test_find() {
+    let m = convert_regex_to_nfa(&parse_regex("ab+").unwrap());
+    assert_eq!(find(&m, "xxabbbyab"), Some(2..6));
+    assert_eq!(find(&m, "a b"), None);
+    assert_eq!(find(&m, ""), None);
+
+    // leftmost wins over longest
+    let m = convert_regex_to_nfa(&parse_regex("a|bcdef").unwrap());
+    assert_eq!(find(&m, "xbcdefa"), Some(1..6));
+    assert_eq!(find(&m, "abcdef"), Some(0..1));
+
+    // among matches with the same start, the longest wins regardless of alternation order
+    let m = convert_regex_to_nfa(&parse_regex("a|ab|abc").unwrap());
+    assert_eq!(find(&m, "xabcd"), Some(1..4));
+    let m = convert_regex_to_nfa(&parse_regex("(a|ab)(c|bcd)").unwrap());
+    assert_eq!(find(&m, "abcd"), Some(0..4));
+
+    // offsets are in bytes
+    let m = convert_regex_to_nfa(&parse_regex("é+").unwrap());
+    assert_eq!(find(&m, "caféé!"), Some(3..7));
+}
+
+#[test]
+fn test_find_empty_matches() {
+    let m = convert_regex_to_nfa(&parse_regex("a*").unwrap());
+    assert_eq!(find(&m, "baa"), Some(0..0));
+    assert_eq!(find(&m, ""), Some(0..0));
+
+    let matches: Vec<_> = find_iter(&m, "aab").collect();
+    assert_eq!(matches, vec![0..2, 3..3]);
+
+    let matches: Vec<_> = find_iter(&m, "bab").collect();
+    assert_eq!(matches, vec![0..0, 1..2, 3..3]);
+
+    let matches: Vec<_> = find_iter(&m, "").collect();
+    assert_eq!(matches, vec![0..0]);
+
+    let matches: Vec<_> = find_iter(&m, "ééa").collect();
+    assert_eq!(matches, vec![0..0, 2..2, 4..5]);
+}
+
+#[test]
+fn test_find_iter() {
+    let m = convert_regex_to_nfa(&parse_regex("\\d+").unwrap());
+    let input = "10 apples, 200 pears and 3 plums";
+    let matches: Vec<&str> = find_iter(&m, input).map(|r| &input[r]).collect();
+    assert_eq!(matches, vec!["10", "200", "3"]);
+
+    // matches never overlap
+    let m = convert_regex_to_nfa(&parse_regex("aba").unwrap());
+    let matches: Vec<_> = find_iter(&m, "ababababa").collect();
+    assert_eq!(matches, vec![0..3, 4..7]);
+
+    let m = convert_regex_to_nfa(&parse_regex("z").unwrap());
+    assert_eq!(find_iter(&m, "abc").count(), 0);
+}
+
+#[test]
+fn test_captures() {
+    let m = convert_regex_to_nfa(&parse_regex("(\\w+)@(\\w+)").unwrap());
+    assert_eq!(num_capture_groups(&m), 3);
+    assert_eq!(
+        captures(&m, "mail: alice@example now"),
+        Some(vec![Some(6..19), Some(6..11), Some(12..19)])
+    );
+    assert_eq!(captures(&m, "no at sign"), None);
+
+    // a group which does not take part in the match has no span
+    let m = convert_regex_to_nfa(&parse_regex("(a)|(b)").unwrap());
+    assert_eq!(captures(&m, "b"), Some(vec![Some(0..1), None, Some(0..1)]));
+
+    // a repeated group reports its last iteration
+    let m = convert_regex_to_nfa(&parse_regex("(?:(\\d)-)+").unwrap());
+    assert_eq!(captures(&m, "1-2-3-"), Some(vec![Some(0..6), Some(4..5)]));
+
+    // nfas without groups only have group 0
+    let m = convert_regex_to_nfa(&parse_regex("ab").unwrap());
+    assert_eq!(captures(&m, "cab"), Some(vec![Some(1..3)]));
+}
+
+#[test]
+fn test_captures_priority() {
+    // the overall match is leftmost-longest, and the groups come from the highest priority run
+    let m = convert_regex_to_nfa(&parse_regex("(a|ab)(c|bcd)").unwrap());
+    assert_eq!(
+        captures(&m, "abcd"),
+        Some(vec![Some(0..4), Some(0..1), Some(1..4)])
+    );
+
+    let m = convert_regex_to_nfa(&parse_regex("(a*)(a*)").unwrap());
+    assert_eq!(
+        captures(&m, "aaa"),
+        Some(vec![Some(0..3), Some(0..3), Some(3..3)])
+    );
+
+    let m = convert_regex_to_nfa(&parse_regex("(x*)|(x)").unwrap());
+    assert_eq!(captures(&m, "x"), Some(vec![Some(0..1), Some(0..1), None]));
+
+    let m = convert_regex_to_nfa(&parse_regex("(a?)(ab)?b").unwrap());
+    assert_eq!(captures(&m, "ab"), Some(vec![Some(0..2), Some(0..1), None]));
+}
+
+#[test]
+fn test_captures_agree_with_find() {
+    for pattern in [
+        "a|bcdef",
+        "(a|ab)(c|bcd)",
+        "x*",
+        "(\\d+)(\\.\\d+)?",
+        "[ab]+b",
+    ] {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        for input in ["", "xbcdefa", "abcd", "3.14 and 42", "aabab", "xxy"] {
+            let spans: Vec<_> = captures_iter(&m, input)
+                .map(|c| c[0].clone().unwrap())
+                .collect();
+            let expected_spans: Vec<_> = find_iter(&m, input).collect();
+            assert_eq!(spans, expected_spans, "{} on {:?}", pattern, input);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+mod tests;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::{
+    char_class::{negate_ranges, normalize_ranges},
+    dfa::{dfa_to_nfa, Dfa},
+    CharClass, Label, Nfa, RegexExpr, State,
+};
+
+/// The ranges matched by '.', every character except a newline.
+const ANY_CHAR_RANGES: &[(char, char)] = &[('\0', '\u{9}'), ('\u{b}', char::MAX)];
+
+/// Converts a NFA into an expression accepting the same strings, or returns None if the NFA
+/// accepts nothing.
+///
+/// The NFA gets a new initial and accepting state, and then every other state is eliminated in
+/// turn: the paths through a state q are replaced by edges labelled with expressions, where an
+/// edge p -> q -> r becomes p -> r labelled (p,q)(q,q)*(q,r). The states are eliminated in the
+/// order of the heuristic of Delgado and Morais, always picking the state whose elimination grows
+/// the expressions the least, so the result stays reasonably small.
+///
+/// `Label::Save` transitions are treated as epsilon transitions, so the result has no capturing
+/// groups. An expression for only the empty string is written as `.{0}`.
+pub fn nfa_to_regex(m: &Nfa) -> Option<RegexExpr> {
+    let useful = useful_states(m);
+    if !useful.contains(&m.initial_state) {
+        return None;
+    }
+
+    let new_init_state = useful.iter().max().unwrap() + 1;
+    let new_acc_state = new_init_state + 1;
+    let mut graph = EdgeGraph::default();
+    graph.add(new_init_state, m.initial_state, Edge::Epsilon);
+    graph.add(m.accepting_state, new_acc_state, Edge::Epsilon);
+    for (q, out_transitions) in m.transitions.iter() {
+        for (label, r) in out_transitions {
+            if useful.contains(q) && useful.contains(r) {
+                graph.add(*q, *r, label_edge(label));
+            }
+        }
+    }
+
+    let mut remaining: BTreeSet<State> = useful.into_iter().collect();
+    while let Some(q) = remaining
+        .iter()
+        .copied()
+        .min_by_key(|q| (graph.elimination_weight(*q), *q))
+    {
+        graph.eliminate(q);
+        remaining.remove(&q);
+    }
+
+    Some(
+        match graph.edges.remove(&(new_init_state, new_acc_state))? {
+            Edge::Epsilon => RegexExpr::Repeat(Box::new(RegexExpr::AnyChar), 0, Some(0)),
+            Edge::Expr(expr) => expr,
+        },
+    )
+}
+
+/// Converts a dfa into an expression accepting the same strings, or returns None if the dfa
+/// accepts nothing. Converting the minimal dfa usually gives a simpler expression than converting
+/// the NFA it was built from.
+pub fn dfa_to_regex(dfa: &Dfa) -> Option<RegexExpr> {
+    nfa_to_regex(&dfa_to_nfa(dfa))
+}
+
+/// Returns true if an expression matches the empty string.
+pub fn nullable(expr: &RegexExpr) -> bool {
+    match expr {
+        RegexExpr::SingleChar(_) | RegexExpr::Class(_) | RegexExpr::AnyChar => false,
+        RegexExpr::Star(_) | RegexExpr::Optional(_) => true,
+        RegexExpr::Plus(e) | RegexExpr::Group(e, _) => nullable(e),
+        RegexExpr::Repeat(e, min, _) => *min == 0 || nullable(e),
+        RegexExpr::Concat(e1, e2) | RegexExpr::And(e1, e2) => nullable(e1) && nullable(e2),
+        RegexExpr::Or(e1, e2) => nullable(e1) || nullable(e2),
+        RegexExpr::Not(e) => !nullable(e),
+    }
+}
+
+/// The states which lie on some path from the initial state to the accepting state.
+fn useful_states(m: &Nfa) -> HashSet<State> {
+    let mut predecessors: BTreeMap<State, Vec<State>> = BTreeMap::new();
+    for (q, out_transitions) in m.transitions.iter() {
+        for (_, r) in out_transitions {
+            predecessors.entry(*r).or_default().push(*q);
+        }
+    }
+
+    let reachable = search(m.initial_state, |q| {
+        m.transitions
+            .get(&q)
+            .into_iter()
+            .flatten()
+            .map(|(_, r)| *r)
+            .collect()
+    });
+    let co_reachable = search(m.accepting_state, |q| {
+        predecessors.get(&q).cloned().unwrap_or_default()
+    });
+    reachable.intersection(&co_reachable).copied().collect()
+}
+
+/// Returns every state reachable from `start` by following `neighbours`.
+fn search(start: State, neighbours: impl Fn(State) -> Vec<State>) -> HashSet<State> {
+    let mut seen = HashSet::from([start]);
+    let mut stack = vec![start];
+    while let Some(q) = stack.pop() {
+        for r in neighbours(q) {
+            if seen.insert(r) {
+                stack.push(r);
+            }
+        }
+    }
+    seen
+}
+
+/// The expression labelling an edge during state elimination. The empty string needs a case of
+/// its own, as no `RegexExpr` stands for exactly the empty string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Edge {
+    Epsilon,
+    Expr(RegexExpr),
+}
+
+fn label_edge(label: &Label) -> Edge {
+    match label {
+        Label::Epsilon | Label::Save(_) => Edge::Epsilon,
+        Label::Char(c) => Edge::Expr(RegexExpr::SingleChar(*c)),
+        Label::Class(ranges) => Edge::Expr(class_expr(ranges.clone())),
+    }
+}
+
+/// A graph with at most one edge between any two states, kept in sorted maps so that the
+/// expression built does not depend on hashing order.
+#[derive(Default)]
+struct EdgeGraph {
+    edges: BTreeMap<(State, State), Edge>,
+    successors: BTreeMap<State, BTreeSet<State>>,
+    predecessors: BTreeMap<State, BTreeSet<State>>,
+}
+
+impl EdgeGraph {
+    /// Adds an edge from q to r, joining it with any existing edge by an OR.
+    fn add(&mut self, q: State, r: State, edge: Edge) {
+        let edge = match self.edges.remove(&(q, r)) {
+            Some(existing) => union(existing, edge),
+            None => edge,
+        };
+        self.edges.insert((q, r), edge);
+        self.successors.entry(q).or_default().insert(r);
+        self.predecessors.entry(r).or_default().insert(q);
+    }
+
+    fn neighbours(map: &BTreeMap<State, BTreeSet<State>>, q: State) -> Vec<State> {
+        map.get(&q)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|r| *r != q)
+            .collect()
+    }
+
+    /// Estimates by how much eliminating q grows the total size of the expressions: each of the
+    /// i incoming and o outgoing edges is copied into i * o new edges, with the loop on q in each.
+    fn elimination_weight(&self, q: State) -> usize {
+        let ins = EdgeGraph::neighbours(&self.predecessors, q);
+        let outs = EdgeGraph::neighbours(&self.successors, q);
+        let in_size: usize = ins.iter().map(|p| edge_size(&self.edges[&(*p, q)])).sum();
+        let out_size: usize = outs.iter().map(|r| edge_size(&self.edges[&(q, *r)])).sum();
+        let loop_size = self.edges.get(&(q, q)).map_or(0, edge_size);
+
+        (in_size * outs.len() + out_size * ins.len() + loop_size * ins.len() * outs.len())
+            .saturating_sub(in_size + out_size + loop_size)
+    }
+
+    /// Removes q, replacing every path p -> q -> r by a direct edge.
+    fn eliminate(&mut self, q: State) {
+        let ins = EdgeGraph::neighbours(&self.predecessors, q);
+        let outs = EdgeGraph::neighbours(&self.successors, q);
+        let loop_edge = self.edges.remove(&(q, q)).map(star);
+
+        for p in ins.iter() {
+            for r in outs.iter() {
+                let through = match loop_edge.clone() {
+                    Some(loop_edge) => concat(loop_edge, self.edges[&(q, *r)].clone()),
+                    None => self.edges[&(q, *r)].clone(),
+                };
+                let path = concat(self.edges[&(*p, q)].clone(), through);
+                self.add(*p, *r, path);
+            }
+        }
+
+        for p in ins {
+            self.edges.remove(&(p, q));
+            self.successors.get_mut(&p).unwrap().remove(&q);
+        }
+        for r in outs {
+            self.edges.remove(&(q, r));
+            self.predecessors.get_mut(&r).unwrap().remove(&q);
+        }
+        self.successors.remove(&q);
+        self.predecessors.remove(&q);
+    }
+}
+
+fn edge_size(edge: &Edge) -> usize {
+    match edge {
+        Edge::Epsilon => 1,
+        Edge::Expr(expr) => expr_size(expr),
+    }
+}
+
+/// The number of nodes of an expression.
+fn expr_size(expr: &RegexExpr) -> usize {
+    match expr {
+        RegexExpr::SingleChar(_) | RegexExpr::Class(_) | RegexExpr::AnyChar => 1,
+        RegexExpr::Star(e)
+        | RegexExpr::Plus(e)
+        | RegexExpr::Optional(e)
+        | RegexExpr::Repeat(e, _, _)
+        | RegexExpr::Group(e, _)
+        | RegexExpr::Not(e) => 1 + expr_size(e),
+        RegexExpr::Concat(e1, e2) | RegexExpr::Or(e1, e2) | RegexExpr::And(e1, e2) => {
+            1 + expr_size(e1) + expr_size(e2)
+        }
+    }
+}
+
+// The constructors below tidy up the expressions as they are built: they drop the empty string
+// where it makes no difference, merge alternatives of single characters into one class, write
+// rr* as r+ and keep both binary operators right nested, the way `parse_regex` nests them.
+
+fn union(e1: Edge, e2: Edge) -> Edge {
+    match (e1, e2) {
+        (Edge::Epsilon, Edge::Epsilon) => Edge::Epsilon,
+        (Edge::Epsilon, Edge::Expr(e)) | (Edge::Expr(e), Edge::Epsilon) => Edge::Expr(optional(e)),
+        (Edge::Expr(e1), Edge::Expr(e2)) => Edge::Expr(or(e1, e2)),
+    }
+}
+
+fn concat(e1: Edge, e2: Edge) -> Edge {
+    match (e1, e2) {
+        (Edge::Epsilon, e) | (e, Edge::Epsilon) => e,
+        (Edge::Expr(e1), Edge::Expr(e2)) => Edge::Expr(concat_exprs(e1, e2)),
+    }
+}
+
+fn star(edge: Edge) -> Edge {
+    match edge {
+        Edge::Epsilon => Edge::Epsilon,
+        Edge::Expr(RegexExpr::Star(e) | RegexExpr::Plus(e) | RegexExpr::Optional(e)) => {
+            Edge::Expr(RegexExpr::Star(e))
+        }
+        Edge::Expr(e) => Edge::Expr(RegexExpr::Star(Box::new(e))),
+    }
+}
+
+fn optional(expr: RegexExpr) -> RegexExpr {
+    match expr {
+        e if nullable(&e) => e,
+        RegexExpr::Plus(e) => RegexExpr::Star(e),
+        e => RegexExpr::Optional(Box::new(e)),
+    }
+}
+
+fn or(e1: RegexExpr, e2: RegexExpr) -> RegexExpr {
+    if e1 == e2 {
+        return e1;
+    }
+    match (e1, e2) {
+        (RegexExpr::Or(a, b), e2) => or(*a, or(*b, e2)),
+        (e1, RegexExpr::Or(a, b)) => match char_union(&e1, &a) {
+            Some(merged) => or(merged, *b),
+            None => RegexExpr::Or(Box::new(e1), Box::new(RegexExpr::Or(a, b))),
+        },
+        (e1, e2) => match char_union(&e1, &e2) {
+            Some(merged) => merged,
+            None => RegexExpr::Or(Box::new(e1), Box::new(e2)),
+        },
+    }
+}
+
+fn concat_exprs(e1: RegexExpr, e2: RegexExpr) -> RegexExpr {
+    match (e1, e2) {
+        (RegexExpr::Concat(a, b), e2) => concat_exprs(*a, concat_exprs(*b, e2)),
+        (e1, RegexExpr::Star(e)) if e1 == *e => RegexExpr::Plus(e),
+        (RegexExpr::Star(e), e2) if *e == e2 => RegexExpr::Plus(e),
+        (e1, RegexExpr::Concat(a, b)) if matches!(&*a, RegexExpr::Star(e) if **e == e1) => {
+            let RegexExpr::Star(e) = *a else {
+                unreachable!()
+            };
+            RegexExpr::Concat(Box::new(RegexExpr::Plus(e)), b)
+        }
+        (e1, e2) => RegexExpr::Concat(Box::new(e1), Box::new(e2)),
+    }
+}
+
+/// If both expressions match single characters only, returns one expression matching the
+/// characters of either.
+fn char_union(e1: &RegexExpr, e2: &RegexExpr) -> Option<RegexExpr> {
+    let mut ranges = char_ranges(e1)?;
+    ranges.extend(char_ranges(e2)?);
+    Some(class_expr(ranges))
+}
+
+/// Returns the ranges of the characters matched by an expression which matches a single
+/// character, or None for any other expression.
+pub fn char_ranges(expr: &RegexExpr) -> Option<Vec<(char, char)>> {
+    match expr {
+        RegexExpr::SingleChar(c) => Some(vec![(*c, *c)]),
+        RegexExpr::Class(class) => Some(class.matched_ranges()),
+        RegexExpr::AnyChar => Some(ANY_CHAR_RANGES.to_vec()),
+        _ => None,
+    }
+}
+
+/// Returns the simplest expression matching a single character of the given ranges: the
+/// character itself, '.', or whichever of the class and its negation lists fewer ranges.
+/// No ranges at all give the negation of every character, as parsed from "[^\\s\\S]".
+pub fn class_expr(ranges: Vec<(char, char)>) -> RegexExpr {
+    let ranges = normalize_ranges(ranges);
+    let negated_ranges = negate_ranges(&ranges);
+    match ranges[..] {
+        [] => RegexExpr::Class(CharClass::new(negated_ranges, true)),
+        [(start, end)] if start == end => RegexExpr::SingleChar(start),
+        _ if ranges == ANY_CHAR_RANGES => RegexExpr::AnyChar,
+        _ if negated_ranges.len() < ranges.len() => {
+            RegexExpr::Class(CharClass::new(negated_ranges, true))
+        }
+        _ => RegexExpr::Class(CharClass::new(ranges, false)),
+    }
+}
@@ -0,0 +1,93 @@
+#![cfg(test)]
+
+use super::{dfa_to_regex, nfa_to_regex, nullable};
+use crate::{
+    dfa::{determinize, minimize},
+    language::{equivalent, Equivalence},
+    stage_1::parse_regex,
+    stage_2::convert_regex_to_nfa,
+    Nfa,
+};
+
+fn nfa(pattern: &str) -> Nfa {
+    convert_regex_to_nfa(&parse_regex(pattern).unwrap())
+}
+
+fn simplify(pattern: &str) -> String {
+    let dfa = minimize(&determinize(&nfa(pattern)).unwrap());
+    dfa_to_regex(&dfa).unwrap().to_string()
+}
+
+#[test]
+fn test_nfa_to_regex_preserves_language() {
+    for pattern in [
+        "a",
+        "(a|b)*abb",
+        "(a*)|(bc)(d*)",
+        "[a-z_][a-z0-9_]*",
+        "\\d{2,4}-?x+",
+        "(ab|a)(bc|c)",
+        "[^aeiou]*.",
+        "x{0}",
+        "\\w+&~(.*admin.*)",
+        "((a|b)(a|b))*",
+    ] {
+        let m = nfa(pattern);
+        for converted in [
+            nfa_to_regex(&m).unwrap(),
+            dfa_to_regex(&minimize(&determinize(&m).unwrap())).unwrap(),
+        ] {
+            let printed = converted.to_string();
+            assert_eq!(
+                equivalent(&m, &nfa(&printed)),
+                Ok(Equivalence::Equal),
+                "{} became {}",
+                pattern,
+                printed
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dfa_to_regex_simplifies() {
+    assert_eq!(simplify("a+|aa*"), "a+");
+    assert_eq!(simplify("(a*)*b"), "a*b");
+    assert_eq!(simplify("a|b|c|[d-f]"), "[a-f]");
+    assert_eq!(simplify("(ab)*a"), "a(?:ba)*");
+    assert_eq!(simplify("[^a]|[^b]"), "[\\0-\\u{10ffff}]");
+    assert_eq!(simplify("\\d+(\\.\\d+)?"), "[0-9]+(?:\\.[0-9]+)?");
+    assert_eq!(simplify("a{0}"), ".{0}");
+}
+
+#[test]
+fn test_nfa_to_regex_empty_language() {
+    assert_eq!(nfa_to_regex(&nfa("a&b")), None);
+    assert_eq!(
+        dfa_to_regex(&minimize(&determinize(&nfa("~(.|\\n)*")).unwrap())),
+        None
+    );
+}
+
+#[test]
+fn test_nullable() {
+    for (pattern, expected) in [
+        ("a*", true),
+        ("a+", false),
+        ("(a?)+", true),
+        ("a{0,2}", true),
+        ("ab?", false),
+        ("a?b?", true),
+        ("a|b*", true),
+        ("~a", true),
+        ("~a*", false),
+        ("a*&b?", true),
+    ] {
+        assert_eq!(
+            nullable(&parse_regex(pattern).unwrap()),
+            expected,
+            "{}",
+            pattern
+        );
+    }
+}
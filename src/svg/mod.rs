@@ -0,0 +1,366 @@
+mod tests;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crate::{nfa_to_file::display_label, stage_2::rename_states, Label, Nfa, State};
+
+/// The radius of the circle drawn for a state.
+const RADIUS: f64 = 18.0;
+/// The horizontal distance between the centers of two consecutive layers.
+const LAYER_GAP: f64 = 100.0;
+/// The vertical distance between the centers of two consecutive states of a layer.
+const ROW_GAP: f64 = 70.0;
+/// The length of the arrow pointing to the initial state.
+const START_ARROW: f64 = 30.0;
+/// The empty space around the drawing.
+const MARGIN: f64 = 20.0;
+const FONT_SIZE: f64 = 14.0;
+
+/// Draws a NFA as a SVG image, laid out from left to right like `rankdir=LR` in Graphviz, without
+/// needing any external program.
+///
+/// States are numbered like in `write_nfa_to_file` and placed in layers by their distance from
+/// the initial state, with the accepting state alone in the last layer. Transitions going to the
+/// next layer are straight lines, and all others (like the back edges created by `Star`) are
+/// curves, so edges in opposite directions between two states do not overlap. Parallel
+/// transitions are drawn as a single edge whose label lists all their labels, separated by
+/// commas.
+pub fn write_nfa_to_svg(m: &Nfa, mut out: impl Write) -> io::Result<()> {
+    let mut m = m.clone();
+    let num_states = rename_states(&mut m);
+    let layout = Layout::new(&m, num_states);
+
+    let mut body = String::new();
+    let mut bounds = Bounds::new();
+
+    // the arrow coming from nowhere into the initial state
+    let (x, y) = layout.position[m.initial_state];
+    bounds.add(x - RADIUS - START_ARROW, y);
+    draw_path(
+        &mut body,
+        &format!(
+            "M {:.1} {:.1} L {:.1} {:.1}",
+            x - RADIUS - START_ARROW,
+            y,
+            x - RADIUS,
+            y
+        ),
+    );
+
+    for ((from, to), labels) in merged_edges(&m) {
+        let label = labels.join(",");
+        let (label_x, label_y) = if from == to {
+            draw_loop(&mut body, &mut bounds, layout.position[from])
+        } else {
+            let curved = layout.layer[to] != layout.layer[from] + 1;
+            draw_edge(
+                &mut body,
+                &mut bounds,
+                layout.position[from],
+                layout.position[to],
+                curved,
+            )
+        };
+        let half_width = label.chars().count() as f64 * FONT_SIZE * 0.3;
+        bounds.add(label_x - half_width, label_y - FONT_SIZE);
+        bounds.add(label_x + half_width, label_y);
+        writeln!(
+            body,
+            "  <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            label_x,
+            label_y,
+            escape_xml(&label)
+        )
+        .unwrap();
+    }
+
+    for (q, (x, y)) in layout.position.iter().enumerate() {
+        bounds.add(x - RADIUS, y - RADIUS);
+        bounds.add(x + RADIUS, y + RADIUS);
+        draw_circle(&mut body, *x, *y, RADIUS);
+        if q == m.accepting_state {
+            draw_circle(&mut body, *x, *y, RADIUS - 4.0);
+        }
+        writeln!(
+            body,
+            "  <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x,
+            y + FONT_SIZE * 0.35,
+            q
+        )
+        .unwrap();
+    }
+
+    let (min_x, min_y) = (bounds.min_x - MARGIN, bounds.min_y - MARGIN);
+    let (width, height) = (
+        bounds.max_x - bounds.min_x + 2.0 * MARGIN,
+        bounds.max_y - bounds.min_y + 2.0 * MARGIN,
+    );
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.1}\" height=\"{:.1}\" \
+         viewBox=\"{:.1} {:.1} {:.1} {:.1}\" font-family=\"sans-serif\" font-size=\"{}\">",
+        width, height, min_x, min_y, width, height, FONT_SIZE
+    )?;
+    writeln!(out, "  <defs>")?;
+    writeln!(
+        out,
+        "    <marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+         markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">"
+    )?;
+    writeln!(out, "      <path d=\"M 0 0 L 10 5 L 0 10 z\"/>")?;
+    writeln!(out, "    </marker>")?;
+    writeln!(out, "  </defs>")?;
+    out.write_all(body.as_bytes())?;
+    writeln!(out, "</svg>")
+}
+
+/// Where each state is drawn.
+struct Layout {
+    /// The index of the layer of each state, from left to right.
+    layer: Vec<usize>,
+    /// The center of the circle of each state.
+    position: Vec<(f64, f64)>,
+}
+
+impl Layout {
+    /// Lays out a NFA whose states are numbered 0 to `num_states - 1`.
+    fn new(m: &Nfa, num_states: usize) -> Layout {
+        let layer = assign_layers(m, num_states);
+        let num_layers = layer.iter().max().map_or(0, |l| l + 1);
+        let mut layers: Vec<Vec<State>> = vec![Vec::new(); num_layers];
+        for q in 0..num_states {
+            layers[layer[q]].push(q);
+        }
+        order_layers(m, &mut layers, &layer);
+
+        let tallest = layers.iter().map(|l| l.len()).max().unwrap_or(0);
+        let mut position = vec![(0.0, 0.0); num_states];
+        for (l, states) in layers.iter().enumerate() {
+            // every layer is centered on the same horizontal line
+            let offset = (tallest - states.len()) as f64 / 2.0;
+            for (row, q) in states.iter().enumerate() {
+                position[*q] = (l as f64 * LAYER_GAP, (row as f64 + offset) * ROW_GAP);
+            }
+        }
+        Layout { layer, position }
+    }
+}
+
+/// Puts every state in the layer of its distance from the initial state, except for the
+/// accepting state which gets a layer of its own after all others.
+fn assign_layers(m: &Nfa, num_states: usize) -> Vec<usize> {
+    let mut layer: Vec<Option<usize>> = vec![None; num_states];
+    let mut queue = VecDeque::from([m.initial_state]);
+    layer[m.initial_state] = Some(0);
+    while let Some(q) = queue.pop_front() {
+        for (_, next) in m.transitions.get(&q).into_iter().flatten() {
+            if layer[*next].is_none() {
+                layer[*next] = Some(layer[q].unwrap() + 1);
+                queue.push_back(*next);
+            }
+        }
+    }
+
+    // states which cannot be reached are drawn in the first layer
+    let mut layer: Vec<usize> = layer.into_iter().map(|l| l.unwrap_or(0)).collect();
+    let last = (0..num_states)
+        .filter(|q| *q != m.accepting_state)
+        .map(|q| layer[q] + 1)
+        .max()
+        .unwrap_or(0);
+    layer[m.accepting_state] = last;
+    layer
+}
+
+/// Orders the states of each layer to reduce edge crossings, with a few sweeps of the barycenter
+/// heuristic: each state moves to the average row of its neighbours in the previous layer.
+fn order_layers(m: &Nfa, layers: &mut [Vec<State>], layer: &[usize]) {
+    let mut neighbours: Vec<Vec<State>> = vec![Vec::new(); layer.len()];
+    for (q, out_transitions) in m.transitions.iter() {
+        for (_, next) in out_transitions {
+            neighbours[*q].push(*next);
+            neighbours[*next].push(*q);
+        }
+    }
+
+    let mut row = vec![0.0; layer.len()];
+    for states in layers.iter() {
+        for (i, q) in states.iter().enumerate() {
+            row[*q] = i as f64;
+        }
+    }
+    for _ in 0..4 {
+        for (l, states) in layers.iter_mut().enumerate().skip(1) {
+            let barycenter = |q: State| {
+                let rows: Vec<f64> = neighbours[q]
+                    .iter()
+                    .filter(|p| layer[**p] + 1 == l)
+                    .map(|p| row[*p])
+                    .collect();
+                if rows.is_empty() {
+                    row[q]
+                } else {
+                    rows.iter().sum::<f64>() / rows.len() as f64
+                }
+            };
+            // the sort is stable, so states with the same barycenter keep their order
+            let mut keyed: Vec<(f64, State)> =
+                states.iter().map(|q| (barycenter(*q), *q)).collect();
+            keyed.sort_by(|(b1, _), (b2, _)| b1.total_cmp(b2));
+            *states = keyed.into_iter().map(|(_, q)| q).collect();
+            for (i, q) in states.iter().enumerate() {
+                row[*q] = i as f64;
+            }
+        }
+    }
+}
+
+/// Groups the transitions by their source and target states, keeping the labels of each group.
+fn merged_edges(m: &Nfa) -> BTreeMap<(State, State), Vec<String>> {
+    let mut edges: BTreeMap<(State, State), Vec<String>> = BTreeMap::new();
+    let mut seen: HashMap<(State, State), Vec<&Label>> = HashMap::new();
+    for (q, out_transitions) in m.transitions.iter() {
+        for (label, next) in out_transitions {
+            let labels = seen.entry((*q, *next)).or_default();
+            if !labels.contains(&label) {
+                labels.push(label);
+                edges
+                    .entry((*q, *next))
+                    .or_default()
+                    .push(display_label(label));
+            }
+        }
+    }
+    edges
+}
+
+/// The smallest rectangle containing everything drawn so far.
+struct Bounds {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Bounds {
+    fn new() -> Bounds {
+        Bounds {
+            min_x: f64::INFINITY,
+            min_y: f64::INFINITY,
+            max_x: f64::NEG_INFINITY,
+            max_y: f64::NEG_INFINITY,
+        }
+    }
+
+    fn add(&mut self, x: f64, y: f64) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+}
+
+fn draw_circle(body: &mut String, x: f64, y: f64, r: f64) {
+    writeln!(
+        body,
+        "  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"white\" stroke=\"black\"/>",
+        x, y, r
+    )
+    .unwrap();
+}
+
+fn draw_path(body: &mut String, d: &str) {
+    writeln!(
+        body,
+        "  <path d=\"{}\" fill=\"none\" stroke=\"black\" marker-end=\"url(#arrow)\"/>",
+        d
+    )
+    .unwrap();
+}
+
+/// Draws an edge between two distinct states, as a straight line or as a curve bending to the
+/// left of its direction. Returns where its label goes.
+fn draw_edge(
+    body: &mut String,
+    bounds: &mut Bounds,
+    (x1, y1): (f64, f64),
+    (x2, y2): (f64, f64),
+    curved: bool,
+) -> (f64, f64) {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let (mx, my) = ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+
+    // the control point of the curve, which is the midpoint for a straight line
+    let bend = if curved {
+        0.25 * length + 0.5 * ROW_GAP
+    } else {
+        0.0
+    };
+    let (cx, cy) = (mx + dy / length * bend, my - dx / length * bend);
+
+    // the edge starts and ends on the circles, heading to and coming from the control point
+    let toward = |(x, y): (f64, f64), (tx, ty): (f64, f64)| {
+        let d = ((tx - x).powi(2) + (ty - y).powi(2)).sqrt();
+        (x + (tx - x) / d * RADIUS, y + (ty - y) / d * RADIUS)
+    };
+    let (sx, sy) = toward((x1, y1), (cx, cy));
+    let (ex, ey) = toward((x2, y2), (cx, cy));
+    bounds.add(cx, cy);
+
+    if curved {
+        draw_path(
+            body,
+            &format!(
+                "M {:.1} {:.1} Q {:.1} {:.1} {:.1} {:.1}",
+                sx, sy, cx, cy, ex, ey
+            ),
+        );
+        // the middle of the curve, a bit further out
+        let (lx, ly) = (
+            0.25 * sx + 0.5 * cx + 0.25 * ex,
+            0.25 * sy + 0.5 * cy + 0.25 * ey,
+        );
+        let side = if cy < my { -4.0 } else { FONT_SIZE };
+        (lx, ly + side)
+    } else {
+        draw_path(
+            body,
+            &format!("M {:.1} {:.1} L {:.1} {:.1}", sx, sy, ex, ey),
+        );
+        (mx, my - 4.0)
+    }
+}
+
+/// Draws an edge from a state to itself, as a loop above the state. Returns where its label goes.
+fn draw_loop(body: &mut String, bounds: &mut Bounds, (x, y): (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (RADIUS * 0.5, RADIUS * 0.87);
+    let top = y - RADIUS * 3.0;
+    bounds.add(x, top);
+    draw_path(
+        body,
+        &format!(
+            "M {:.1} {:.1} C {:.1} {:.1} {:.1} {:.1} {:.1} {:.1}",
+            x - dx,
+            y - dy,
+            x - RADIUS * 1.2,
+            top,
+            x + RADIUS * 1.2,
+            top,
+            x + dx,
+            y - dy
+        ),
+    );
+    (x, y - RADIUS * 2.4 - 4.0)
+}
+
+/// Escapes the characters with a meaning in XML text.
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,76 @@
+#![cfg(test)]
+
+use std::collections::{HashMap, HashSet};
+
+use super::write_nfa_to_svg;
+use crate::{stage_1::parse_regex, stage_2::convert_regex_to_nfa, Label, Nfa};
+
+fn svg(m: &Nfa) -> String {
+    let mut out = Vec::new();
+    write_nfa_to_svg(m, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_write_nfa_to_svg() {
+    let m = convert_regex_to_nfa(&parse_regex("(a|b*)c").unwrap());
+    let svg = svg(&m);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+
+    // a circle for every state, and an inner one for the accepting state
+    let num_states = m.transitions.len() + 1;
+    assert_eq!(svg.matches("<circle").count(), num_states + 1);
+
+    // an arrow for every transition, the one pointing to the initial state, and the marker
+    // (parallel transitions share an arrow)
+    let edges: HashSet<(usize, usize)> = m
+        .transitions
+        .iter()
+        .flat_map(|(q, t)| t.iter().map(|(_, next)| (*q, *next)))
+        .collect();
+    let num_edges = edges.len();
+    assert_eq!(svg.matches("<path").count(), num_edges + 2);
+    assert_eq!(svg.matches("marker-end").count(), num_edges + 1);
+
+    // the back edge of the star is curved
+    assert!(svg.contains(" Q "), "{}", svg);
+    // "a" and the ε skipping "b*" both go from the initial state to the end of the group
+    for label in ["a,ε", "b", "c", "ε"] {
+        assert!(svg.contains(&format!(">{}</text>", label)), "{}", svg);
+    }
+}
+
+#[test]
+fn test_write_nfa_to_svg_merges_parallel_edges() {
+    let m = Nfa {
+        initial_state: 0,
+        accepting_state: 2,
+        transitions: HashMap::from([
+            (
+                0,
+                vec![
+                    (Label::Char('a'), 1),
+                    (Label::Char('<'), 1),
+                    (Label::Char('a'), 1),
+                ],
+            ),
+            (1, vec![(Label::Char('&'), 1), (Label::Epsilon, 2)]),
+        ]),
+    };
+    let svg = svg(&m);
+    assert!(svg.contains(">a,&lt;</text>"), "{}", svg);
+    // the self loop is a cubic curve
+    assert!(svg.contains(" C "), "{}", svg);
+    assert!(svg.contains(">&amp;</text>"), "{}", svg);
+    assert_eq!(svg.matches("marker-end").count(), 4);
+}
+
+#[test]
+fn test_write_nfa_to_svg_is_deterministic() {
+    for pattern in ["(ab|a)*c", "[a-z]+@[a-z]+\\.com", "x{2,4}|y"] {
+        let m = convert_regex_to_nfa(&parse_regex(pattern).unwrap());
+        assert_eq!(svg(&m), svg(&m.clone()));
+        assert!(!svg(&m).contains("NaN"), "{}", pattern);
+    }
+}
@@ -11,13 +11,22 @@ lines = file_contents.split("\n")
 dot = graphviz.Digraph('round-table', comment = 'The Round Table')
 dot.attr(rankdir='LR')  
 
+#reading the initial and accepting states
+accepting = str(int(lines[0]) - 1)
+for i in lines[1:]:
+    if i.startswith("accepting "):
+        accepting = i.split(" ")[1]
+
 #graphing the nodes
 for i in range(int(lines[0])):
-    dot.node(str(i))
+    if str(i) == accepting:
+        dot.node(str(i), shape = "doublecircle")
+    else:
+        dot.node(str(i))
     
 #graphing the edges
 for i in lines[1:]:
-    if i!="":
+    if i!="" and not i.startswith("initial ") and not i.startswith("accepting "):
         i = i.split(" ")
         if (i[2] == "\\0"):
             i[2] ="Є"